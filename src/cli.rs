@@ -0,0 +1,654 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+use crate::exchange::Interval;
+use crate::limits::Limits;
+use crate::montecarlo::Resample;
+use crate::optimizer::{Axis, Grid};
+use crate::report::Metric;
+use crate::resample::Resampler;
+use crate::risk::RiskRules;
+use crate::sizing::PositionSizing;
+use crate::timeframe::TrendFilter;
+use crate::trading::Parameters;
+
+pub const USAGE: &str = "\
+Usage: trading-rs [COMMAND] [OPTIONS]
+
+Commands:
+    live               Trade the symbols in cache.toml (default)
+    paper              Like `live`, but simulate orders against cached balances
+    backtest           Run the strategy over historical klines
+    optimize           Search strategy parameters over historical klines
+    walk-forward       Optimize on rolling windows and test on the klines after each
+    download           Download historical klines into the local store
+    balance            Print account balances of the assets in cache.toml
+    validate-config    Check config.txt and cache.toml
+    help               Print this message
+
+Options:
+    --symbols <LIST>         Comma separated symbols, e.g. BNB/USDT,BTCUSDT, in `live` and
+                             `paper` a subset of cache.toml [default: all of it]
+    --interval <I>           Kline interval, `download` also accepts a comma separated list,
+                             e.g. 1h,4h [default: 1h]
+    --start <YYYY-MM-DD>     Start of the historical range, not in `live` and `paper`
+    --end <YYYY-MM-DD>       End of the historical range [default: now]
+    --dir <PATH>             Kline store directory [default: ./data]
+    --trend <LIST>           Higher timeframe filters, entries only while each closes above
+                             its moving average, e.g. 4h,1d:sma:20 [default MA: ema:50]
+    --source-interval <I>    Backtest on klines resampled from the stored ones of this
+                             lower interval, e.g. 1m
+
+Risk options, checked on every closed kline in `live`, `paper` and backtests:
+    --stop-loss <X>          Below the entry price, e.g. 5% or 2atr
+    --trailing-stop <X>      Below the highest close since the entry, e.g. 3% or 3atr
+    --take-profit <X>        Above the entry price, e.g. 10% or 4atr
+    --max-holding <N>        Exit N klines after the entry
+    --atr-period <N>         Period of the ATR of the above [default: 14]
+
+Position sizing options:
+    --sizing <METHOD>        even, fraction:<X>, quote:<AMOUNT>, volatility:<RISK>[:<ATR>]
+                             or kelly[:<CAP>] [default: even, volatility ATR: 2, kelly: 0.25]
+    --max-allocation <X>     Largest fraction of the equity in one symbol
+
+Live and paper limits, checked before every entry:
+    --max-daily-loss <X>     Fraction of the equity at the start of the UTC day that trips
+                             the kill switch
+    --max-drawdown <X>       Fraction of the highest equity that trips the kill switch
+    --max-positions <N>      Open positions at once
+    --max-exposure <X>       Largest fraction of the equity in one asset
+    --max-orders <N>         Orders per hour
+    --on-kill <ACTION>       stop or liquidate [default: stop]
+    --reference <ASSET>      Asset the portfolio and the limits are valued in [default: USDT]
+
+Strategy parameters, `optimize` and `walk-forward` also accept <MIN>:<MAX>:<STEP> ranges:
+    --dmi-period <N>         [default: 14, optimize: 10:20:2]
+    --adx-ma <TYPE>          sma, ema, dema, tema, wma, hma, kama, smma or vwma [default: dema]
+    --dema-period <N>        Period of the ADX moving average [default: 9, optimize: 5:13:2]
+    --bb-period <N>          [default: 20]
+    --bb-multiplier <X>      [default: 2, optimize: 1.5:2.5:0.25]
+    --adx-trend <X>          [default: 15, optimize: 10:20:5]
+    --adx-strong <X>         [default: 25, optimize: 20:30:5]
+    --adx-extreme <X>        [default: 40, optimize: 35:50:5]
+
+Optimizer options:
+    --metric <METRIC>        roi, sharpe, drawdown, win-rate or profit-factor [default: sharpe]
+    --samples <N>            Random search over N combinations instead of the full grid
+    --seed <N>               Seed of the random search or resampling [default: current time]
+    --threads <N>            Number of worker threads [default: CPU count]
+    --top <N>                Number of ranked results to print [default: 10]
+
+Backtest options:
+    --monte-carlo <RUNS>     Resample the backtest trades RUNS times
+    --resample <METHOD>      shuffle, bootstrap or skip[:<RATE>] [default: bootstrap]
+    --ruin <X>               Fraction of the equity lost that counts as ruin [default: 0.5]
+
+Walk-forward options:
+    --in-sample <DAYS>       Length of the optimized windows [default: 180]
+    --out-of-sample <DAYS>   Length of the tested windows and the step between them [default: 30]
+";
+
+/// Quote assets used to split symbols given without a `/` separator.
+const QUOTE_ASSETS: [&str; 9] = [
+    "USDT", "BUSD", "USDC", "TUSD", "BTC", "ETH", "BNB", "EUR", "TRY",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Live(Options),
+    Paper(Options),
+    Backtest(Options),
+    Optimize(Options),
+    WalkForward(Options),
+    Download(Options),
+    Balance,
+    ValidateConfig,
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    /// `(base, quote)` pairs.
+    pub symbols: Vec<(String, String)>,
+    pub intervals: Vec<Interval>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub dir: PathBuf,
+    pub parameters: Parameters,
+    pub trend_filters: Vec<TrendFilter>,
+    pub source_interval: Option<Interval>,
+    pub risk: RiskRules,
+    pub sizing: PositionSizing,
+    pub limits: Limits,
+    /// Asset the live portfolio is valued in.
+    pub reference: String,
+    pub grid: Grid,
+    pub metric: Metric,
+    pub samples: Option<usize>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+    pub top: usize,
+    pub in_sample_days: i64,
+    pub out_of_sample_days: i64,
+    pub monte_carlo: Option<usize>,
+    pub resample: Resample,
+    pub ruin: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            symbols: Vec::new(),
+            intervals: Vec::new(),
+            start_time: None,
+            end_time: None,
+            dir: PathBuf::from("./data"),
+            parameters: Parameters::default(),
+            trend_filters: Vec::new(),
+            source_interval: None,
+            risk: RiskRules::default(),
+            sizing: PositionSizing::default(),
+            limits: Limits::default(),
+            reference: String::from("USDT"),
+            grid: Grid::default(),
+            metric: Metric::Sharpe,
+            samples: None,
+            seed: None,
+            threads: None,
+            top: 10,
+            in_sample_days: 180,
+            out_of_sample_days: 30,
+            monte_carlo: None,
+            resample: Resample::Bootstrap,
+            ruin: 0.5,
+        }
+    }
+}
+
+impl Options {
+    /// The given interval, one hour if none was given.
+    pub fn interval(&self) -> Interval {
+        self.intervals.first().copied().unwrap_or(Interval::Hour(1))
+    }
+
+    pub fn start_time(&self) -> Result<DateTime<Utc>, String> {
+        self.start_time
+            .ok_or_else(|| String::from("`--start` is required"))
+    }
+
+    pub fn end_time(&self) -> DateTime<Utc> {
+        self.end_time.unwrap_or_else(Utc::now)
+    }
+
+    pub fn symbol_strings(&self) -> Vec<String> {
+        self.symbols
+            .iter()
+            .map(|(base, quote)| format!("{}{}", base, quote))
+            .collect()
+    }
+}
+
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+
+    let command = match args.next() {
+        None => return Ok(Command::Live(Options::default())),
+        Some(command) => command,
+    };
+
+    if command.starts_with("--") {
+        let options = parse_options(std::iter::once(command).chain(args), "live")?;
+        return Ok(Command::Live(options));
+    }
+
+    match command.as_str() {
+        "live" => Ok(Command::Live(parse_options(args, &command)?)),
+        "paper" => Ok(Command::Paper(parse_options(args, &command)?)),
+        "backtest" => Ok(Command::Backtest(parse_options(args, &command)?)),
+        "optimize" => Ok(Command::Optimize(parse_options(args, &command)?)),
+        "walk-forward" => Ok(Command::WalkForward(parse_options(args, &command)?)),
+        "download" => Ok(Command::Download(parse_options(args, &command)?)),
+        "balance" => Ok(Command::Balance),
+        "validate-config" => Ok(Command::ValidateConfig),
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        _ => Err(format!("Unknown command `{}`", command)),
+    }
+}
+
+/// Parses the options of `command`, which decides whether the optimizer,
+/// walk-forward and backtest options are accepted.
+fn parse_options<I: Iterator<Item = String>>(
+    mut args: I,
+    command: &str,
+) -> Result<Options, String> {
+    let walk_forward = command == "walk-forward";
+    let optimize = walk_forward || command == "optimize";
+    let backtest = command == "backtest";
+    let live = command == "live" || command == "paper";
+    let mut options = Options::default();
+
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for `{}`", flag))?;
+        let p = &mut options.parameters;
+        let grid = &mut options.grid;
+
+        match flag.as_str() {
+            "--symbols" | "--symbol" => {
+                options.symbols = value
+                    .split(',')
+                    .map(parse_symbol)
+                    .collect::<Result<_, _>>()?
+            }
+            "--interval" | "--intervals" => {
+                options.intervals = value.split(',').map(str::parse).collect::<Result<_, _>>()?
+            }
+            "--start" if !live => options.start_time = Some(parse_date(&value)?),
+            "--end" if !live => options.end_time = Some(parse_date(&value)?),
+            "--dir" => options.dir = PathBuf::from(value),
+            "--trend" => {
+                options.trend_filters =
+                    value.split(',').map(str::parse).collect::<Result<_, _>>()?
+            }
+            "--source-interval" if optimize || backtest => {
+                options.source_interval = Some(value.parse()?)
+            }
+            "--stop-loss" => options.risk.stop_loss = Some(value.parse()?),
+            "--trailing-stop" => options.risk.trailing_stop = Some(value.parse()?),
+            "--take-profit" => options.risk.take_profit = Some(value.parse()?),
+            "--max-holding" => {
+                options.risk.max_holding = match parse_number(&flag, &value)? {
+                    0 => return Err(format!("`{}` must be a positive number of klines", flag)),
+                    klines => Some(klines),
+                }
+            }
+            "--atr-period" => {
+                options.risk.atr_period = match parse_number(&flag, &value)? {
+                    0 => return Err(format!("`{}` must be a positive integer", flag)),
+                    period => period,
+                }
+            }
+            "--sizing" => options.sizing.sizing = value.parse()?,
+            "--max-allocation" => {
+                options.sizing.max_allocation = Some(parse_fraction(&flag, &value)?)
+            }
+            "--max-daily-loss" if live => {
+                options.limits.max_daily_loss = Some(parse_fraction(&flag, &value)?)
+            }
+            "--max-drawdown" if live => {
+                options.limits.max_drawdown = Some(parse_fraction(&flag, &value)?)
+            }
+            "--max-positions" if live => {
+                options.limits.max_positions = Some(parse_number(&flag, &value)?)
+            }
+            "--max-exposure" if live => {
+                options.limits.max_exposure = Some(parse_fraction(&flag, &value)?)
+            }
+            "--max-orders" if live => {
+                options.limits.max_orders = Some(parse_number(&flag, &value)?)
+            }
+            "--on-kill" if live => {
+                options.limits.liquidate = match value.as_str() {
+                    "stop" => false,
+                    "liquidate" => true,
+                    _ => return Err(format!("Invalid value `{}` for `{}`", value, flag)),
+                }
+            }
+            "--reference" if live => options.reference = value.to_uppercase(),
+            "--dmi-period" => {
+                grid.dmi_period = parse_period(&flag, &value, optimize)?;
+                p.dmi_period = grid.dmi_period.min as usize;
+            }
+            "--adx-ma" => {
+                p.adx_ma = value.parse()?;
+                grid.adx_ma = p.adx_ma;
+            }
+            "--dema-period" => {
+                grid.dema_period = parse_period(&flag, &value, optimize)?;
+                p.dema_period = grid.dema_period.min as usize;
+            }
+            "--bb-period" => {
+                grid.bb_period = parse_period(&flag, &value, optimize)?;
+                p.bb_period = grid.bb_period.min as usize;
+            }
+            "--bb-multiplier" => {
+                grid.bb_multiplier = parse_axis(&flag, &value, optimize)?;
+                p.bb_multiplier = grid.bb_multiplier.min;
+            }
+            "--adx-trend" => {
+                grid.adx_trend = parse_axis(&flag, &value, optimize)?;
+                p.adx_trend = grid.adx_trend.min;
+            }
+            "--adx-strong" => {
+                grid.adx_strong = parse_axis(&flag, &value, optimize)?;
+                p.adx_strong = grid.adx_strong.min;
+            }
+            "--adx-extreme" => {
+                grid.adx_extreme = parse_axis(&flag, &value, optimize)?;
+                p.adx_extreme = grid.adx_extreme.min;
+            }
+            "--metric" if optimize => options.metric = value.parse()?,
+            "--samples" if optimize => options.samples = Some(parse_number(&flag, &value)?),
+            "--seed" if optimize || backtest => options.seed = Some(parse_number(&flag, &value)?),
+            "--threads" if optimize => options.threads = Some(parse_number(&flag, &value)?),
+            "--top" if optimize => options.top = parse_number(&flag, &value)?,
+            "--monte-carlo" if backtest => options.monte_carlo = Some(parse_number(&flag, &value)?),
+            "--resample" if backtest => options.resample = value.parse()?,
+            "--ruin" if backtest => {
+                options.ruin = match parse_number(&flag, &value)? {
+                    ruin if ruin > 0f64 && ruin <= 1f64 => ruin,
+                    _ => return Err(format!("`{}` must be in (0, 1]", flag)),
+                }
+            }
+            "--in-sample" if walk_forward => options.in_sample_days = parse_days(&flag, &value)?,
+            "--out-of-sample" if walk_forward => {
+                options.out_of_sample_days = parse_days(&flag, &value)?
+            }
+            _ => return Err(format!("Unknown option `{}`", flag)),
+        }
+    }
+
+    if command != "download" && options.intervals.len() > 1 {
+        return Err(String::from(
+            "`--interval` accepts a list only with `download`",
+        ));
+    }
+
+    // Higher timeframes are built out of whole trading klines, and those out
+    // of whole source klines.
+    let interval = options.interval();
+    for filter in options.trend_filters.iter() {
+        Resampler::new(interval, filter.interval)?;
+    }
+    if let Some(source) = options.source_interval {
+        Resampler::new(source, interval)?;
+    }
+
+    Ok(options)
+}
+
+fn parse_axis(flag: &str, value: &str, optimize: bool) -> Result<Axis, String> {
+    let axis: Axis = value.parse().map_err(|e| format!("{} for `{}`", e, flag))?;
+
+    if !optimize && !axis.is_single() {
+        return Err(format!("`{}` accepts ranges only with `optimize`", flag));
+    }
+
+    Ok(axis)
+}
+
+fn parse_period(flag: &str, value: &str, optimize: bool) -> Result<Axis, String> {
+    let axis = parse_axis(flag, value, optimize)?;
+
+    if axis.min < 1f64 || axis.min.fract() != 0f64 || axis.step.fract() != 0f64 {
+        return Err(format!("`{}` must be a positive integer", flag));
+    }
+
+    Ok(axis)
+}
+
+fn parse_fraction(flag: &str, value: &str) -> Result<f64, String> {
+    match parse_number(flag, value)? {
+        x if x > 0f64 && x <= 1f64 => Ok(x),
+        _ => Err(format!("`{}` must be in (0, 1]", flag)),
+    }
+}
+
+fn parse_days(flag: &str, value: &str) -> Result<i64, String> {
+    match parse_number(flag, value)? {
+        days if days > 0 => Ok(days),
+        _ => Err(format!("`{}` must be a positive number of days", flag)),
+    }
+}
+
+/// Parses `BNB/USDT` or `BNBUSDT` into `("BNB", "USDT")`.
+pub fn parse_symbol(s: &str) -> Result<(String, String), String> {
+    let s = s.trim().to_uppercase();
+
+    if let Some((base, quote)) = s.split_once('/') {
+        if !base.is_empty() && !quote.is_empty() {
+            return Ok((base.to_string(), quote.to_string()));
+        }
+    } else if let Some(quote) = QUOTE_ASSETS
+        .iter()
+        .find(|quote| s.len() > quote.len() && s.ends_with(*quote))
+    {
+        let base = &s[..s.len() - quote.len()];
+        return Ok((base.to_string(), quote.to_string()));
+    }
+
+    Err(format!(
+        "Invalid symbol `{}`, write it as BASE/QUOTE, e.g. BNB/USDT",
+        s
+    ))
+}
+
+pub fn parse_date(s: &str) -> Result<DateTime<Utc>, String> {
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date `{}`, expected YYYY-MM-DD", s))?;
+
+    Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()))
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value `{}` for `{}`", value, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::ma::MaType;
+    use crate::risk::Distance;
+    use crate::sizing::Sizing;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parse_backtest() {
+        let command = parse(args(
+            "backtest --symbols bnbusdt,ETH/BTC --interval 4h --start 2021-01-15 --adx-strong 30 --bb-period 14 --trend 1d,1w:sma:10",
+        ))
+        .unwrap();
+
+        let options = match command {
+            Command::Backtest(options) => options,
+            _ => panic!("Expected backtest"),
+        };
+
+        assert_eq!(
+            options.symbols,
+            vec![
+                (String::from("BNB"), String::from("USDT")),
+                (String::from("ETH"), String::from("BTC"))
+            ]
+        );
+        assert_eq!(options.interval().to_string(), "4h");
+        assert_eq!(
+            options.start_time().unwrap(),
+            parse_date("2021-01-15").unwrap()
+        );
+        assert_eq!(options.parameters.adx_strong, 30f64);
+        assert_eq!(options.parameters.adx_trend, 15f64);
+        assert_eq!(options.parameters.bb_period, 14);
+        assert_eq!(options.trend_filters.len(), 2);
+        assert_eq!(options.trend_filters[1].period, 10);
+        assert!(parse(args("backtest --interval 4h --trend 1h")).is_err());
+        assert!(parse(args("backtest --interval 4h --trend 6h")).is_err());
+        assert_eq!(options.source_interval, None);
+        assert!(options.risk.is_empty());
+        assert_eq!(options.monte_carlo, None);
+    }
+
+    #[test]
+    fn parse_intervals() {
+        assert!(parse(args("optimize --interval 4h,1d")).is_err());
+        match parse(args("download --interval 4h,1d")).unwrap() {
+            Command::Download(options) => assert_eq!(options.intervals.len(), 2),
+            _ => panic!("Expected download"),
+        }
+    }
+
+    #[test]
+    fn parse_source_interval() {
+        match parse(args("optimize --interval 4h --source-interval 15m")).unwrap() {
+            Command::Optimize(options) => {
+                assert_eq!(options.source_interval, Some(Interval::Minute(15)))
+            }
+            _ => panic!("Expected optimize"),
+        }
+        assert!(parse(args("backtest --interval 4h --source-interval 4h")).is_err());
+        assert!(parse(args("live --source-interval 1m")).is_err());
+    }
+
+    #[test]
+    fn parse_risk() {
+        match parse(args(
+            "live --stop-loss 5% --trailing-stop 2.5atr --max-holding 48",
+        ))
+        .unwrap()
+        {
+            Command::Live(options) => {
+                assert_eq!(options.risk.stop_loss, Some(Distance::Percent(0.05)));
+                assert_eq!(options.risk.trailing_stop, Some(Distance::Atr(2.5)));
+                assert_eq!(options.risk.take_profit, None);
+                assert_eq!(options.risk.max_holding, Some(48));
+            }
+            _ => panic!("Expected live"),
+        }
+        assert!(parse(args("backtest --take-profit 10")).is_err());
+        assert!(parse(args("backtest --max-holding 0")).is_err());
+    }
+
+    #[test]
+    fn parse_sizing() {
+        match parse(args(
+            "backtest --sizing volatility:0.01:3 --max-allocation 0.5",
+        ))
+        .unwrap()
+        {
+            Command::Backtest(options) => {
+                assert_eq!(
+                    options.sizing.sizing,
+                    Sizing::Volatility {
+                        risk: 0.01,
+                        atr: 3f64
+                    }
+                );
+                assert_eq!(options.sizing.max_allocation, Some(0.5));
+            }
+            _ => panic!("Expected backtest"),
+        }
+        assert!(parse(args("backtest --max-allocation 2")).is_err());
+    }
+
+    #[test]
+    fn parse_limits() {
+        match parse(args(
+            "paper --max-drawdown 0.2 --max-orders 6 --on-kill liquidate",
+        ))
+        .unwrap()
+        {
+            Command::Paper(options) => {
+                assert_eq!(options.limits.max_drawdown, Some(0.2));
+                assert_eq!(options.limits.max_orders, Some(6));
+                assert!(options.limits.liquidate);
+            }
+            _ => panic!("Expected paper"),
+        }
+        assert!(parse(args("backtest --max-drawdown 0.2")).is_err());
+        assert!(parse(args("live --on-kill panic")).is_err());
+        assert!(parse(args("live --start 2021-01-15")).is_err());
+        assert!(parse(args("paper --end 2021-01-15")).is_err());
+    }
+
+    #[test]
+    fn parse_reference() {
+        match parse(args("paper")).unwrap() {
+            Command::Paper(options) => assert_eq!(options.reference, "USDT"),
+            _ => panic!("Expected paper"),
+        }
+        match parse(args("live --reference busd")).unwrap() {
+            Command::Live(options) => assert_eq!(options.reference, "BUSD"),
+            _ => panic!("Expected live"),
+        }
+        assert!(parse(args("backtest --reference busd")).is_err());
+    }
+
+    #[test]
+    fn parse_monte_carlo() {
+        match parse(args(
+            "backtest --monte-carlo 1000 --resample skip:0.2 --seed 3",
+        ))
+        .unwrap()
+        {
+            Command::Backtest(options) => {
+                assert_eq!(options.monte_carlo, Some(1000));
+                assert_eq!(options.resample, Resample::Skip(0.2));
+                assert_eq!(options.seed, Some(3));
+            }
+            _ => panic!("Expected backtest"),
+        }
+        assert!(parse(args("optimize --monte-carlo 1000")).is_err());
+        assert!(parse(args("backtest --ruin 1.5")).is_err());
+    }
+
+    #[test]
+    fn parse_optimize() {
+        let command = parse(args(
+            "optimize --symbols BNB/USDT --adx-strong 20:30:5 --dmi-period 14 --adx-ma hma --metric roi --samples 50",
+        ))
+        .unwrap();
+
+        let options = match command {
+            Command::Optimize(options) => options,
+            _ => panic!("Expected optimize"),
+        };
+
+        assert_eq!(options.grid.adx_strong.values(), vec![20f64, 25f64, 30f64]);
+        assert_eq!(options.grid.dmi_period, Axis::single(14f64));
+        assert_eq!(options.grid.dema_period, Grid::default().dema_period);
+        assert_eq!(options.grid.bb_period, Axis::single(20f64));
+        assert_eq!(options.grid.adx_ma, MaType::Hma);
+        assert!(options.trend_filters.is_empty());
+        assert_eq!(options.metric, Metric::Roi);
+        assert_eq!(options.samples, Some(50));
+        assert!(parse(args("optimize --dmi-period 10:20:0.5")).is_err());
+        assert!(parse(args("optimize --in-sample 90")).is_err());
+
+        match parse(args("walk-forward --in-sample 90 --adx-trend 10:20:5")).unwrap() {
+            Command::WalkForward(options) => {
+                assert_eq!(options.in_sample_days, 90);
+                assert_eq!(options.out_of_sample_days, 30);
+                assert_eq!(options.grid.adx_trend.count(), 3);
+            }
+            _ => panic!("Expected walk-forward"),
+        }
+        assert!(parse(args("walk-forward --out-of-sample 0")).is_err());
+    }
+
+    #[test]
+    fn parse_defaults_and_errors() {
+        assert_eq!(
+            parse(Vec::new()).unwrap(),
+            Command::Live(Options::default())
+        );
+        assert_eq!(
+            parse(args("validate-config")).unwrap(),
+            Command::ValidateConfig
+        );
+        assert!(parse(args("trade")).is_err());
+        assert!(parse(args("backtest --start")).is_err());
+        assert!(parse(args("backtest --interval 7x")).is_err());
+        assert!(parse(args("backtest --dmi-period 0")).is_err());
+        assert!(parse(args("backtest --adx-strong 20:30:5")).is_err());
+        assert!(parse(args("backtest --metric roi")).is_err());
+        assert!(parse_symbol("USDT").is_err());
+        assert!(parse_symbol("FOOBAR").is_err());
+    }
+}
@@ -0,0 +1,461 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::exchange::Kline;
+
+const CSV_HEADER: &str = "open_time,open,high,low,close,volume";
+
+// Binance public data switched spot dumps to microsecond timestamps, anything
+// past this value can not be a millisecond timestamp.
+const MICROS_THRESHOLD: i64 = 100_000_000_000_000;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        message: String,
+    },
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::errors::ParquetError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse { line, message } => write!(f, "Line {}: {}", line, message),
+            #[cfg(feature = "parquet")]
+            Error::Parquet(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(e: parquet::errors::ParquetError) -> Self {
+        Error::Parquet(e)
+    }
+}
+
+/// Column positions of the kline fields in a CSV row.
+struct Columns {
+    open_time: usize,
+    open: usize,
+    high: usize,
+    low: usize,
+    close: usize,
+    volume: Option<usize>,
+}
+
+impl Columns {
+    /// Layout of the Binance API and data.binance.vision dumps, which have no
+    /// header row.
+    fn binance() -> Self {
+        Self {
+            open_time: 0,
+            open: 1,
+            high: 2,
+            low: 3,
+            close: 4,
+            volume: Some(5),
+        }
+    }
+
+    fn from_header(header: &str, line: usize) -> Result<Self> {
+        let names: Vec<String> = header
+            .split(',')
+            .map(|name| name.trim().to_ascii_lowercase())
+            .collect();
+
+        let find = |candidates: &[&str]| {
+            names
+                .iter()
+                .position(|name| candidates.contains(&name.as_str()))
+        };
+        let require = |candidates: &[&str]| {
+            find(candidates).ok_or_else(|| Error::Parse {
+                line,
+                message: format!("Missing `{}` column", candidates[0]),
+            })
+        };
+
+        Ok(Self {
+            open_time: require(&["open_time", "timestamp", "time", "date"])?,
+            open: require(&["open"])?,
+            high: require(&["high"])?,
+            low: require(&["low"])?,
+            close: require(&["close"])?,
+            volume: find(&["volume"]),
+        })
+    }
+
+    fn parse(&self, row: &str, line: usize) -> Result<Kline> {
+        let fields: Vec<&str> = row.split(',').map(|field| field.trim()).collect();
+
+        let field = |i: usize| {
+            fields.get(i).copied().ok_or_else(|| Error::Parse {
+                line,
+                message: format!("Expected at least {} columns", i + 1),
+            })
+        };
+        let number = |i: usize| {
+            let s = field(i)?;
+            s.parse::<f64>().map_err(|_| Error::Parse {
+                line,
+                message: format!("Invalid number `{}`", s),
+            })
+        };
+
+        let open_time = field(self.open_time)?;
+        let open_time = open_time.parse::<i64>().map_err(|_| Error::Parse {
+            line,
+            message: format!("Invalid open time `{}`", open_time),
+        })?;
+
+        Ok(Kline {
+            open_time: normalize_time(open_time),
+            open: number(self.open)?,
+            high: number(self.high)?,
+            low: number(self.low)?,
+            close: number(self.close)?,
+            volume: match self.volume {
+                Some(i) => number(i)?,
+                None => 0f64,
+            },
+        })
+    }
+}
+
+fn normalize_time(time: i64) -> i64 {
+    if time >= MICROS_THRESHOLD {
+        time / 1000
+    } else {
+        time
+    }
+}
+
+/// Parses klines from CSV text.
+///
+/// Rows without a header are read in the Binance column order, so kline dumps
+/// from data.binance.vision can be loaded directly. Otherwise the columns are
+/// looked up by name in the header row.
+pub fn parse_csv(text: &str) -> Result<Vec<Kline>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .peekable();
+
+    let columns = match lines.peek() {
+        Some(&(i, first)) => {
+            let first_field = first.split(',').next().unwrap_or(first);
+            let is_header = first_field.trim().parse::<i64>().is_err();
+
+            if is_header {
+                lines.next();
+                Columns::from_header(first, i)?
+            } else {
+                Columns::binance()
+            }
+        }
+        None => return Ok(Vec::new()),
+    };
+
+    lines.map(|(i, line)| columns.parse(line, i)).collect()
+}
+
+pub fn read_csv<P: AsRef<Path>>(path: P) -> Result<Vec<Kline>> {
+    parse_csv(&fs::read_to_string(path)?)
+}
+
+pub fn write_csv<P: AsRef<Path>>(path: P, klines: &[Kline]) -> Result<()> {
+    let mut f = BufWriter::new(fs::File::create(path)?);
+    writeln!(f, "{}", CSV_HEADER)?;
+
+    for kline in klines {
+        writeln!(
+            f,
+            "{},{},{},{},{},{}",
+            kline.open_time, kline.open, kline.high, kline.low, kline.close, kline.volume
+        )?;
+    }
+
+    f.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+const PARQUET_SCHEMA: &str = "
+    message kline {
+        REQUIRED INT64 open_time;
+        REQUIRED DOUBLE open;
+        REQUIRED DOUBLE high;
+        REQUIRED DOUBLE low;
+        REQUIRED DOUBLE close;
+        REQUIRED DOUBLE volume;
+    }
+";
+
+/// Reads klines from a Parquet file, matching columns by name. Integer,
+/// timestamp, floating point and string columns are accepted. Rows count
+/// as lines from 1, a missing column is reported on line 0, the schema.
+#[cfg(feature = "parquet")]
+pub fn read_parquet<P: AsRef<Path>>(path: P) -> Result<Vec<Kline>> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+
+    let reader = SerializedFileReader::new(fs::File::open(path)?)?;
+    let names: Vec<String> = reader
+        .metadata()
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.name().to_ascii_lowercase())
+        .collect();
+    for candidates in [
+        &["open_time", "timestamp", "time"][..],
+        &["open"],
+        &["high"],
+        &["low"],
+        &["close"],
+    ] {
+        if !names.iter().any(|name| candidates.contains(&name.as_str())) {
+            return Err(Error::Parse {
+                line: 0,
+                message: format!("Missing `{}` column", candidates[0]),
+            });
+        }
+    }
+
+    let mut klines = Vec::with_capacity(reader.metadata().file_metadata().num_rows() as usize);
+
+    for (i, row) in reader.get_row_iter(None)?.enumerate() {
+        let row = row?;
+        let mut kline = Kline::default();
+
+        for (name, field) in row.get_column_iter() {
+            let value = match field {
+                Field::Int(v) => *v as f64,
+                Field::Long(v) | Field::TimestampMillis(v) => *v as f64,
+                Field::TimestampMicros(v) => (*v / 1000) as f64,
+                Field::Float(v) => *v as f64,
+                Field::Double(v) => *v,
+                Field::Str(s) => s.parse().map_err(|_| Error::Parse {
+                    line: i + 1,
+                    message: format!("Invalid number `{}` in `{}`", s, name),
+                })?,
+                _ => continue,
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                "open_time" | "timestamp" | "time" => {
+                    kline.open_time = normalize_time(value as i64)
+                }
+                "open" => kline.open = value,
+                "high" => kline.high = value,
+                "low" => kline.low = value,
+                "close" => kline.close = value,
+                "volume" => kline.volume = value,
+                _ => (),
+            }
+        }
+
+        klines.push(kline);
+    }
+
+    Ok(klines)
+}
+
+#[cfg(feature = "parquet")]
+pub fn write_parquet<P: AsRef<Path>>(path: P, klines: &[Kline]) -> Result<()> {
+    use std::sync::Arc;
+
+    use parquet::data_type::{DoubleType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(fs::File::create(path)?, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+
+    let open_times: Vec<i64> = klines.iter().map(|k| k.open_time).collect();
+    let prices: [fn(&Kline) -> f64; 5] =
+        [|k| k.open, |k| k.high, |k| k.low, |k| k.close, |k| k.volume];
+
+    if let Some(mut column) = row_group.next_column()? {
+        column
+            .typed::<Int64Type>()
+            .write_batch(&open_times, None, None)?;
+        column.close()?;
+    }
+
+    for price in prices.iter() {
+        if let Some(mut column) = row_group.next_column()? {
+            let values: Vec<f64> = klines.iter().map(price).collect();
+            column
+                .typed::<DoubleType>()
+                .write_batch(&values, None, None)?;
+            column.close()?;
+        }
+    }
+
+    row_group.close()?;
+    writer.close()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("trading-rs-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn parse_binance_dump() {
+        let text = "\
+1609459200000,28923.63000000,29031.34000000,28690.17000000,28995.13000000,2311.81144500,1609462799999,66768830.34010008,58389,1215.35923500,35103542.52288262,0
+1735689600000000,93576.00000000,93610.93000000,93537.50000000,93610.93000000,8.21827000,1735689659999999,768978.35410790,1158,5.01715000,469415.70935400,0
+";
+        let klines = parse_csv(text).unwrap();
+
+        assert_eq!(klines.len(), 2);
+        assert_eq!(klines[0].open_time, 1609459200000);
+        assert_eq!(klines[0].close, 28995.13f64);
+        assert_eq!(klines[0].volume, 2311.811445f64);
+        assert_eq!(klines[1].open_time, 1735689600000);
+    }
+
+    #[test]
+    fn parse_with_header() {
+        let text = "time,close,open,low,high\n1000,2.5,1,0.5,3\n";
+        let klines = parse_csv(text).unwrap();
+
+        assert_eq!(
+            klines,
+            vec![Kline {
+                open_time: 1000,
+                open: 1f64,
+                high: 3f64,
+                low: 0.5f64,
+                close: 2.5f64,
+                volume: 0f64,
+            }]
+        );
+
+        assert!(parse_csv("time,open,high,low\n1,2,3,4\n").is_err());
+        assert!(parse_csv("1,2,3,4,x,6\n").is_err());
+    }
+
+    #[test]
+    fn csv_roundtrip() {
+        let klines = vec![
+            Kline {
+                open_time: 1620000000000,
+                open: 0.1f64 + 0.2f64,
+                high: 1e-8f64,
+                low: 123456.789f64,
+                close: 42f64,
+                volume: 7.5f64,
+            },
+            Kline::default(),
+        ];
+        let path = path("roundtrip.csv");
+
+        write_csv(&path, &klines).unwrap();
+        assert_eq!(read_csv(&path).unwrap(), klines);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_roundtrip() {
+        let klines = vec![
+            Kline {
+                open_time: 1620000000000,
+                open: 1.5f64,
+                high: 2f64,
+                low: 1f64,
+                close: 1.75f64,
+                volume: 100f64,
+            },
+            Kline {
+                open_time: 1620003600000,
+                open: 1.75f64,
+                high: 1.8f64,
+                low: 0.3f64,
+                close: 0.4f64,
+                volume: 12.25f64,
+            },
+        ];
+        let path = path("roundtrip.parquet");
+
+        write_parquet(&path, &klines).unwrap();
+        assert_eq!(read_parquet(&path).unwrap(), klines);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_missing_column() {
+        use std::sync::Arc;
+
+        use parquet::data_type::{DoubleType, Int64Type};
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+
+        let schema = "
+            message kline {
+                REQUIRED INT64 open_time;
+                REQUIRED DOUBLE open;
+                REQUIRED DOUBLE high;
+                REQUIRED DOUBLE low;
+            }
+        ";
+        let path = path("missing.parquet");
+        let schema = Arc::new(parse_message_type(schema).unwrap());
+        let properties = Arc::new(WriterProperties::builder().build());
+        let file = fs::File::create(&path).unwrap();
+        let mut writer = SerializedFileWriter::new(file, schema, properties).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&[1620000000000], None, None)
+            .unwrap();
+        column.close().unwrap();
+        while let Some(mut column) = row_group.next_column().unwrap() {
+            column
+                .typed::<DoubleType>()
+                .write_batch(&[1f64], None, None)
+                .unwrap();
+            column.close().unwrap();
+        }
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        match read_parquet(&path) {
+            Err(Error::Parse { message, .. }) => assert_eq!(message, "Missing `close` column"),
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::exchange::binance::Account;
+use crate::exchange::{Interval, Kline};
+use crate::store::{self, KlineStore};
+
+const MAX_RETRIES: u32 = 5;
+
+/// Pulls klines of several symbols and intervals into the local
+/// [`KlineStore`].
+///
+/// Every downloaded page is persisted right away and only missing ranges are
+/// requested, so an interrupted download resumes where it stopped when run
+/// again.
+pub struct Downloader {
+    dir: PathBuf,
+    symbols: Vec<String>,
+    intervals: Vec<Interval>,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+impl Downloader {
+    pub fn new(
+        dir: PathBuf,
+        symbols: Vec<String>,
+        intervals: Vec<Interval>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            dir,
+            symbols,
+            intervals,
+            start_time,
+            end_time: end_time.min(Utc::now()),
+        }
+    }
+
+    /// Downloads every symbol and interval pair, returning the number of
+    /// pairs that could not be completed.
+    pub fn run(&self, binance: &Account) -> usize {
+        let mut failed = 0;
+
+        for symbol in self.symbols.iter() {
+            for &interval in self.intervals.iter() {
+                if let Err(e) = self.download(binance, symbol, interval) {
+                    println!("[ERROR] {} {}: {}", symbol, interval, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        failed
+    }
+
+    fn download(
+        &self,
+        binance: &Account,
+        symbol: &str,
+        interval: Interval,
+    ) -> Result<(), store::Error> {
+        let mut store = KlineStore::open(&self.dir, symbol, interval)?;
+        if store.duplicates() > 0 {
+            println!(
+                "[WARN] {} {}: dropped {} duplicate klines",
+                symbol,
+                interval,
+                store.duplicates()
+            );
+        }
+        let step = interval.to_millis();
+        let start = self.start_time.timestamp_millis();
+        let end = self.end_time.timestamp_millis();
+
+        let ranges = store.missing_ranges(start, end);
+        let total: i64 = ranges.iter().map(|(from, to)| (to - from) / step + 1).sum();
+
+        println!(
+            "[INFO] {} {}: {} klines stored, ~{} missing",
+            symbol,
+            interval,
+            store.range(start, end).len(),
+            total
+        );
+
+        store.sync_with(
+            start,
+            end,
+            |store, from, to| fetch_with_retry(store, binance, from, to),
+            |done, next| {
+                println!(
+                    "[INFO] {} {}: {}/{} ({:.1}%) up to {}",
+                    symbol,
+                    interval,
+                    done,
+                    total,
+                    done as f64 / total.max(1) as f64 * 100f64,
+                    Utc.timestamp_millis_opt(next - 1).unwrap(),
+                );
+            },
+        )?;
+
+        for (start, end) in store.gaps() {
+            println!(
+                "[WARN] {} {}: no klines between {} and {}",
+                symbol,
+                interval,
+                Utc.timestamp_millis_opt(start).unwrap(),
+                Utc.timestamp_millis_opt(end).unwrap(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn fetch_with_retry(
+    store: &KlineStore,
+    binance: &Account,
+    start: i64,
+    end: i64,
+) -> Result<Vec<Kline>, store::Error> {
+    let mut attempt = 0;
+
+    loop {
+        match store.fetch_page(binance, start, end) {
+            Ok(klines) => return Ok(klines),
+            Err(e) if attempt < MAX_RETRIES => {
+                attempt += 1;
+                let backoff = Duration::from_secs(2u64.pow(attempt));
+                println!(
+                    "[WARN] {} {}: {}, retrying in {}s ({}/{})",
+                    store.symbol(),
+                    store.interval(),
+                    e,
+                    backoff.as_secs(),
+                    attempt,
+                    MAX_RETRIES
+                );
+                thread::sleep(backoff);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -35,6 +35,13 @@ impl fmt::Display for Error {
 impl std::error::Error for Error {}
 
 impl Error {
+    fn from_request(e: reqwest::Error) -> Self {
+        Self {
+            code: 0,
+            message: e.to_string(),
+        }
+    }
+
     fn from_json(slice: &[u8]) -> Self {
         let mut code = 0;
         let mut message = String::new();
@@ -192,6 +199,15 @@ impl Account {
         end_time: Option<i64>,
         limit: Option<u32>,
     ) -> Result<Response> {
+        // `Interval` can hold lengths Binance does not serve, answer for it
+        // without a round trip.
+        if !interval.is_supported() {
+            return Err(Error {
+                code: -1120,
+                message: format!("Unsupported interval `{}`", interval),
+            });
+        }
+
         let mut url = format!(
             "{}/api/v3/klines?symbol={}&interval={}",
             API_URL, symbol, interval,
@@ -212,7 +228,7 @@ impl Account {
             url.push_str(&parameter);
         }
 
-        let response = self.client.get(&url).send().unwrap();
+        let response = self.client.get(&url).send().map_err(Error::from_request)?;
         let status = response.status();
 
         if status.is_success() {
@@ -426,6 +442,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn unsupported_interval() {
+        let binance = Account {
+            _api_key: String::new(),
+            secret_key: String::new(),
+            client: Client::new(),
+        };
+        let error = binance
+            .get_kline_data("BNBUSDT", Interval::Minute(7), None, None, None)
+            .unwrap_err();
+
+        assert_eq!(error.code, -1120);
+        assert_eq!(error.to_string(), "Unsupported interval `7m`");
+    }
+
     #[test]
     fn new_order() {
         let binance = Account::new();
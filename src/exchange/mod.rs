@@ -1,8 +1,15 @@
 pub mod binance;
 
 use std::fmt;
+use std::str::FromStr;
 
-#[derive(Clone, Copy)]
+use chrono::{Datelike, TimeZone, Utc};
+
+const DAY: i64 = 24 * 60 * 60 * 1000;
+/// The epoch was a Thursday, the first Monday is four days later.
+const FIRST_MONDAY: i64 = 4 * DAY;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Interval {
     Minute(i64),
     Hour(i64),
@@ -23,25 +30,113 @@ impl fmt::Display for Interval {
     }
 }
 
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid interval `{}`", s);
+
+        if s.len() < 2 || !s.is_char_boundary(s.len() - 1) {
+            return Err(invalid());
+        }
+
+        let (count, unit) = s.split_at(s.len() - 1);
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+
+        let interval = match (count, unit) {
+            (_, "m") => Interval::Minute(count),
+            (_, "h") => Interval::Hour(count),
+            (_, "d") => Interval::Day(count),
+            (1, "w") => Interval::Week,
+            (1, "M") => Interval::Month,
+            _ => return Err(invalid()),
+        };
+
+        if !interval.is_supported() {
+            return Err(format!(
+                "Unsupported interval `{}`, expected one of 1m, 3m, 5m, 15m, 30m, 1h, 2h, 4h, 6h, 8h, 12h, 1d, 3d, 1w or 1M",
+                s
+            ));
+        }
+
+        Ok(interval)
+    }
+}
+
 impl Interval {
+    /// Whether Binance serves klines of this interval.
+    pub fn is_supported(&self) -> bool {
+        matches!(
+            self,
+            Interval::Minute(1 | 3 | 5 | 15 | 30)
+                | Interval::Hour(1 | 2 | 4 | 6 | 8 | 12)
+                | Interval::Day(1 | 3)
+                | Interval::Week
+                | Interval::Month
+        )
+    }
+
+    /// Length of the interval, 30 days for months whose real length is only
+    /// known from `next_open_time`.
     pub fn to_millis(&self) -> i64 {
         match self {
             Interval::Minute(m) => *m * 60 * 1000,
             Interval::Hour(h) => *h * 60 * 60 * 1000,
-            Interval::Day(d) => *d * 24 * 60 * 60 * 1000,
-            Interval::Week => 7 * 24 * 60 * 60 * 1000,
-            Interval::Month => 30 * 60 * 60 * 1000,
+            Interval::Day(d) => *d * DAY,
+            Interval::Week => 7 * DAY,
+            Interval::Month => 30 * DAY,
+        }
+    }
+
+    /// Open time of the kline containing `time`, in UTC milliseconds. Klines
+    /// open on multiples of their length counted from the epoch, weeks on
+    /// Monday midnight and months on the first of the month.
+    pub fn open_time(&self, time: i64) -> i64 {
+        match self {
+            Interval::Week => time - (time - FIRST_MONDAY).rem_euclid(7 * DAY),
+            Interval::Month => {
+                let date = Utc.timestamp_millis_opt(time).unwrap();
+                Utc.with_ymd_and_hms(date.year(), date.month(), 1, 0, 0, 0)
+                    .unwrap()
+                    .timestamp_millis()
+            }
+            _ => time - time.rem_euclid(self.to_millis()),
+        }
+    }
+
+    /// Open time of the kline after the one containing `time`.
+    pub fn next_open_time(&self, time: i64) -> i64 {
+        match self {
+            Interval::Month => {
+                let date = Utc.timestamp_millis_opt(time).unwrap();
+                let (year, month) = if date.month() == 12 {
+                    (date.year() + 1, 1)
+                } else {
+                    (date.year(), date.month() + 1)
+                };
+                Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0)
+                    .unwrap()
+                    .timestamp_millis()
+            }
+            _ => self.open_time(time) + self.to_millis(),
         }
     }
+
+    /// Close time of the kline containing `time`, its last millisecond like
+    /// the exchange reports it.
+    pub fn close_time(&self, time: i64) -> i64 {
+        self.next_open_time(time) - 1
+    }
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Kline {
     pub open_time: i64,
     pub open: f64,
     pub high: f64,
     pub low: f64,
     pub close: f64,
+    pub volume: f64,
 }
 
 impl Kline {
@@ -51,6 +146,7 @@ impl Kline {
         self.high = kline.high;
         self.low = kline.low;
         self.close = kline.close;
+        self.volume = kline.volume;
     }
 
     pub fn parse_2d_array(slice: &[u8], capacity: usize) -> Vec<Self> {
@@ -90,8 +186,9 @@ impl Kline {
                     1 => kline.open = s.parse().unwrap(),
                     2 => kline.high = s.parse().unwrap(),
                     3 => kline.low = s.parse().unwrap(),
-                    4 => {
-                        kline.close = s.parse().unwrap();
+                    4 => kline.close = s.parse().unwrap(),
+                    5 => {
+                        kline.volume = s.parse().unwrap();
                         break;
                     }
                     _ => (),
@@ -105,3 +202,52 @@ impl Kline {
         kline
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_from_str() {
+        assert_eq!(
+            "15m".parse::<Interval>().unwrap().to_millis(),
+            15 * 60 * 1000
+        );
+        assert_eq!("4h".parse::<Interval>().unwrap().to_string(), "4h");
+        assert_eq!("1w".parse::<Interval>().unwrap().to_string(), "1w");
+        assert_eq!("1M".parse::<Interval>().unwrap().to_string(), "1M");
+        assert!("2w".parse::<Interval>().is_err());
+        assert!("h".parse::<Interval>().is_err());
+        assert!("1y".parse::<Interval>().is_err());
+        assert!("7m".parse::<Interval>().is_err());
+        assert!("3d".parse::<Interval>().is_ok());
+        assert!(!Interval::Minute(7).is_supported());
+        assert_eq!(Interval::Month.to_millis(), 30 * DAY);
+    }
+
+    fn time(y: i32, m: u32, d: u32, h: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn calendar_boundaries() {
+        // Wednesday the 17th of March 2021.
+        let t = time(2021, 3, 17, 13) + 1234;
+        assert_eq!(Interval::Week.open_time(t), time(2021, 3, 15, 0));
+        assert_eq!(Interval::Week.next_open_time(t), time(2021, 3, 22, 0));
+        assert_eq!(Interval::Month.open_time(t), time(2021, 3, 1, 0));
+        assert_eq!(Interval::Hour(4).open_time(t), time(2021, 3, 17, 12));
+        assert_eq!(Interval::Hour(6).close_time(t), time(2021, 3, 17, 18) - 1);
+        assert_eq!(Interval::Day(3).open_time(t), time(2021, 3, 16, 0));
+        assert_eq!(
+            Interval::Month.next_open_time(time(2020, 12, 31, 23)),
+            time(2021, 1, 1, 0)
+        );
+        assert_eq!(
+            Interval::Month.close_time(time(2020, 2, 1, 0)),
+            time(2020, 3, 1, 0) - 1
+        );
+    }
+}
@@ -0,0 +1,500 @@
+//! Indicators over a whole history at once. Every function feeds the
+//! streaming indicator of the parent module, so element `i` is exactly what
+//! the streaming indicator returns after the `i`th value.
+//!
+//! Indicators that compare a value with the previous one, like [`Rsi`] or
+//! [`Atr`], return `None` for the first element.
+//!
+//! [`series`] runs any other indicator the same way, the functions here are
+//! thin wrappers around it.
+
+use super::divergence::{Divergence, DivergenceDetector};
+use super::ma::{Hma, Kama, Smma, Tema, Vwma, Wma};
+use super::oscillators::{AwesomeOscillator, Cci, Roc, Stochastic, WilliamsR};
+use super::patterns::{CandlePatterns, Pattern};
+use super::trend::{Donchian, Ichimoku, IchimokuLines, Keltner, ParabolicSar, SuperTrend, Trend};
+use super::{
+    Atr, BollingerBand, Dema, Dmi, Ema, Macd, Maximum, Minimum, Rsi, Sma, StandardDeviation,
+    StochRsi, TdSeq, TdSeqValue,
+};
+use crate::exchange::Kline;
+
+pub fn closes(klines: &[Kline]) -> Vec<f64> {
+    klines.iter().map(|k| k.close).collect()
+}
+
+pub fn sma(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Sma::new(period),
+        |sma, &x| {
+            sma.next(x);
+            sma.get()
+        },
+    )
+}
+
+pub fn standard_deviation(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || StandardDeviation::new(period),
+        |std, &x| {
+            std.next(x);
+            std.get()
+        },
+    )
+}
+
+pub fn ema(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Ema::new(period),
+        |ema, &x| {
+            ema.next(x);
+            ema.get()
+        },
+    )
+}
+
+pub fn dema(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Dema::new(period),
+        |dema, &x| {
+            dema.next(x);
+            dema.get()
+        },
+    )
+}
+
+pub fn tema(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Tema::new(period),
+        |tema, &x| {
+            tema.next(x);
+            tema.get()
+        },
+    )
+}
+
+pub fn wma(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Wma::new(period),
+        |wma, &x| {
+            wma.next(x);
+            wma.get()
+        },
+    )
+}
+
+pub fn hma(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Hma::new(period),
+        |hma, &x| {
+            hma.next(x);
+            hma.get()
+        },
+    )
+}
+
+pub fn kama(source: &[f64], period: usize, fast: usize, slow: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Kama::new(period, fast, slow),
+        |kama, &x| {
+            kama.next(x);
+            kama.get()
+        },
+    )
+}
+
+pub fn smma(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Smma::new(period),
+        |smma, &x| {
+            smma.next(x);
+            smma.get()
+        },
+    )
+}
+
+/// Volume weighted average of the closes.
+pub fn vwma(klines: &[Kline], period: usize) -> Vec<Option<f64>> {
+    series(
+        klines,
+        || Vwma::new(period),
+        |vwma, k| {
+            vwma.next(k.close, k.volume);
+            vwma.get()
+        },
+    )
+}
+
+/// `(macd, signal, histogram)` as returned by [`Macd::get`].
+pub fn macd(
+    source: &[f64],
+    fast: usize,
+    slow: usize,
+    signal: usize,
+) -> Vec<(Option<f64>, Option<f64>, Option<f64>)> {
+    series(
+        source,
+        || Macd::new(fast, slow, signal),
+        |macd, &x| {
+            macd.next(x);
+            macd.get()
+        },
+    )
+}
+
+pub fn maximum(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Maximum::new(period),
+        |maximum, &x| {
+            maximum.next(x);
+            maximum.get()
+        },
+    )
+}
+
+pub fn minimum(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Minimum::new(period),
+        |minimum, &x| {
+            minimum.next(x);
+            minimum.get()
+        },
+    )
+}
+
+/// `(basis, upper, lower)` bands.
+pub fn bollinger_band(source: &[f64], period: usize, m: f64) -> Vec<Option<(f64, f64, f64)>> {
+    series(
+        source,
+        || BollingerBand::new(period, m),
+        |bb, &x| {
+            bb.next(x);
+            bb.get()
+        },
+    )
+}
+
+pub fn rsi(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut rsi = Rsi::new(period);
+    with_previous(source, |&x, &prev| {
+        rsi.next(x, prev);
+        rsi.get()
+    })
+}
+
+pub fn stoch_rsi(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut stoch_rsi = StochRsi::new(period);
+    with_previous(source, |&x, &prev| {
+        stoch_rsi.next(x, prev);
+        stoch_rsi.get()
+    })
+}
+
+/// `(%K, %D)` as returned by [`Stochastic::get`].
+pub fn stochastic(
+    klines: &[Kline],
+    period: usize,
+    k_smoothing: usize,
+    d_period: usize,
+) -> Vec<(Option<f64>, Option<f64>)> {
+    series(
+        klines,
+        || Stochastic::new(period, k_smoothing, d_period),
+        |stochastic, k| {
+            stochastic.next(k.high, k.low, k.close);
+            stochastic.get()
+        },
+    )
+}
+
+pub fn williams_r(klines: &[Kline], period: usize) -> Vec<Option<f64>> {
+    series(
+        klines,
+        || WilliamsR::new(period),
+        |williams_r, k| {
+            williams_r.next(k.high, k.low, k.close);
+            williams_r.get()
+        },
+    )
+}
+
+pub fn cci(klines: &[Kline], period: usize) -> Vec<Option<f64>> {
+    series(
+        klines,
+        || Cci::new(period),
+        |cci, k| {
+            cci.next(k.high, k.low, k.close);
+            cci.get()
+        },
+    )
+}
+
+pub fn roc(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Roc::new(period),
+        |roc, &x| {
+            roc.next(x);
+            roc.get()
+        },
+    )
+}
+
+pub fn momentum(source: &[f64], period: usize) -> Vec<Option<f64>> {
+    series(
+        source,
+        || Roc::new(period),
+        |roc, &x| {
+            roc.next(x);
+            roc.momentum()
+        },
+    )
+}
+
+pub fn awesome_oscillator(klines: &[Kline], fast: usize, slow: usize) -> Vec<Option<f64>> {
+    series(
+        klines,
+        || AwesomeOscillator::new(fast, slow),
+        |ao, k| {
+            ao.next(k.high, k.low);
+            ao.get()
+        },
+    )
+}
+
+pub fn atr(klines: &[Kline], period: usize) -> Vec<Option<f64>> {
+    let mut atr = Atr::new(period);
+    with_previous(klines, |kline, prev| {
+        atr.next(kline.high, prev.close, kline.low);
+        atr.get()
+    })
+}
+
+/// `(adx, +di, -di)` as returned by [`Dmi::get`], all `None` for the first
+/// kline.
+pub fn dmi(klines: &[Kline], period: usize) -> Vec<(Option<f64>, Option<f64>, Option<f64>)> {
+    let mut dmi = Dmi::new(period);
+    with_previous(klines, |kline, prev| {
+        dmi.next(kline.high, prev.high, kline.low, prev.low, prev.close);
+        Some(dmi.get())
+    })
+    .into_iter()
+    .map(|value| value.unwrap_or((None, None, None)))
+    .collect()
+}
+
+/// `(upper, middle, lower)` channel.
+pub fn donchian(klines: &[Kline], period: usize) -> Vec<Option<(f64, f64, f64)>> {
+    series(
+        klines,
+        || Donchian::new(period),
+        |donchian, kline| {
+            donchian.next(kline.high, kline.low);
+            donchian.get()
+        },
+    )
+}
+
+/// `(middle, upper, lower)` bands.
+pub fn keltner(
+    klines: &[Kline],
+    period: usize,
+    atr_period: usize,
+    m: f64,
+) -> Vec<Option<(f64, f64, f64)>> {
+    series(
+        klines,
+        || Keltner::new(period, atr_period, m),
+        |keltner, kline| {
+            keltner.next(kline.high, kline.low, kline.close);
+            keltner.get()
+        },
+    )
+}
+
+pub fn supertrend(klines: &[Kline], period: usize, m: f64) -> Vec<Option<(f64, Trend)>> {
+    series(
+        klines,
+        || SuperTrend::new(period, m),
+        |supertrend, kline| {
+            supertrend.next(kline.high, kline.low, kline.close);
+            supertrend.get()
+        },
+    )
+}
+
+pub fn parabolic_sar(klines: &[Kline], step: f64, max_step: f64) -> Vec<Option<f64>> {
+    series(
+        klines,
+        || ParabolicSar::new(step, max_step),
+        |sar, kline| {
+            sar.next(kline.high, kline.low);
+            sar.get()
+        },
+    )
+}
+
+pub fn ichimoku(
+    klines: &[Kline],
+    tenkan: usize,
+    kijun: usize,
+    senkou: usize,
+    displacement: usize,
+) -> Vec<Option<IchimokuLines>> {
+    series(
+        klines,
+        || Ichimoku::new(tenkan, kijun, senkou, displacement),
+        |ichimoku, kline| {
+            ichimoku.next(kline.high, kline.low);
+            ichimoku.get()
+        },
+    )
+}
+
+/// Patterns completed by each kline.
+pub fn candle_patterns(klines: &[Kline]) -> Vec<Vec<Pattern>> {
+    series(klines, CandlePatterns::new, |patterns, k| {
+        patterns.next(k);
+        patterns.get().to_vec()
+    })
+}
+
+/// Every divergence between the klines and the indicator values next to
+/// them, in the order they are confirmed.
+pub fn divergences(
+    klines: &[Kline],
+    indicator: &[Option<f64>],
+    left: usize,
+    right: usize,
+    max_distance: usize,
+) -> Vec<Divergence> {
+    let mut detector = DivergenceDetector::new(left, right, max_distance);
+    let mut divergences = Vec::new();
+
+    for (k, &value) in klines.iter().zip(indicator.iter()) {
+        detector.next(k.high, k.low, value);
+        divergences.extend_from_slice(detector.get());
+    }
+
+    divergences
+}
+
+pub fn td_seq(klines: &[Kline]) -> Vec<TdSeqValue> {
+    series(klines, TdSeq::new, |td_seq, kline| {
+        td_seq.next(kline.high, kline.low, kline.close);
+        td_seq.get()
+    })
+}
+
+/// Feeds `source` one element at a time to the indicator built by `new`,
+/// collecting what `step` returns after each.
+pub fn series<T, I, U>(
+    source: &[T],
+    new: impl FnOnce() -> I,
+    mut step: impl FnMut(&mut I, &T) -> U,
+) -> Vec<U> {
+    let mut indicator = new();
+    source.iter().map(|x| step(&mut indicator, x)).collect()
+}
+
+/// Maps every element but the first together with its predecessor, the first
+/// element maps to `None`.
+fn with_previous<T, U>(source: &[T], mut f: impl FnMut(&T, &T) -> Option<U>) -> Vec<Option<U>> {
+    let mut values = Vec::with_capacity(source.len());
+
+    if !source.is_empty() {
+        values.push(None);
+    }
+
+    values.extend(source.windows(2).map(|w| f(&w[1], &w[0])));
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    fn klines(n: usize) -> Vec<Kline> {
+        let mut rng = Rng::new(35);
+        let mut close = 100f64;
+
+        (0..n as i64)
+            .map(|i| {
+                let open = close;
+                close += rng.next_f64() * 4f64 - 2f64;
+                Kline {
+                    open_time: i * 60_000,
+                    open,
+                    high: open.max(close) + rng.next_f64(),
+                    low: open.min(close) - rng.next_f64(),
+                    close,
+                    volume: 1f64,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batch_matches_streaming() {
+        let klines = klines(500);
+        let closes = closes(&klines);
+
+        let bb_batch = bollinger_band(&closes, 20, 2f64);
+        let rsi_batch = rsi(&closes, 14);
+        let dmi_batch = dmi(&klines, 14);
+        let mut bb_stream = BollingerBand::new(20, 2f64);
+        let mut rsi_stream = Rsi::new(14);
+        let mut dmi_stream = Dmi::new(14);
+
+        assert_eq!(rsi_batch[0], None);
+        assert_eq!(dmi_batch[0], (None, None, None));
+
+        for i in 1..klines.len() {
+            bb_stream.next(closes[i - 1]);
+            assert_eq!(bb_batch[i - 1], bb_stream.get());
+
+            rsi_stream.next(closes[i], closes[i - 1]);
+            assert_eq!(rsi_batch[i], rsi_stream.get());
+
+            let (kline, prev) = (&klines[i], &klines[i - 1]);
+            dmi_stream.next(kline.high, prev.high, kline.low, prev.low, prev.close);
+            assert_eq!(dmi_batch[i], dmi_stream.get());
+        }
+
+        assert!(bb_batch[..19].iter().all(Option::is_none));
+        assert!(bb_batch[19..].iter().all(Option::is_some));
+        assert_eq!(sma(&[1f64, 2f64, 12f64], 3), vec![None, None, Some(5f64)]);
+        assert!(atr(&[], 14).is_empty());
+    }
+
+    #[test]
+    fn series_warms_up() {
+        let step = |sma: &mut Sma, &x: &f64| {
+            sma.next(x);
+            sma.get()
+        };
+
+        assert_eq!(
+            series(&[1f64, 2f64, 3f64, 4f64], || Sma::new(3), step),
+            vec![None, None, Some(2f64), Some(3f64)]
+        );
+        // Shorter than the period, every element is still warming up.
+        assert_eq!(
+            series(&[1f64, 2f64], || Sma::new(3), step),
+            vec![None, None]
+        );
+        assert!(series(&[], || Sma::new(3), step).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+//! Swing points and divergences between the price and an indicator.
+//!
+//! A swing is only known `right` klines after it, so both are reported late.
+//! Indices count the klines fed so far, starting at 0.
+
+use std::collections::VecDeque;
+
+use super::patterns::Direction;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwingKind {
+    High,
+    Low,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Swing {
+    pub kind: SwingKind,
+    pub index: usize,
+    pub value: f64,
+}
+
+/// Pivot highs and lows: a high above the `left` highs before it and not
+/// below the `right` highs after it, likewise for lows.
+pub struct Swings {
+    left: usize,
+    right: usize,
+    tick: usize,
+    /// `(high, low)` of the last `left + right + 1` klines.
+    window: VecDeque<(f64, f64)>,
+    swings: Vec<Swing>,
+}
+
+impl Swings {
+    pub fn new(left: usize, right: usize) -> Self {
+        Self {
+            left,
+            right,
+            tick: 0,
+            window: VecDeque::with_capacity(left + right + 1),
+            swings: Vec::with_capacity(2),
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64) {
+        if self.window.len() == self.left + self.right + 1 {
+            self.window.pop_front();
+        }
+        self.window.push_back((high, low));
+        self.tick += 1;
+        self.swings.clear();
+
+        if self.window.len() < self.left + self.right + 1 {
+            return;
+        }
+
+        let index = self.tick - 1 - self.right;
+        let (center_high, center_low) = self.window[self.left];
+        let before = self.window.iter().take(self.left);
+        let after = self.window.iter().skip(self.left + 1);
+
+        if before.clone().all(|&(h, _)| h < center_high)
+            && after.clone().all(|&(h, _)| h <= center_high)
+        {
+            self.swings.push(Swing {
+                kind: SwingKind::High,
+                index,
+                value: center_high,
+            });
+        }
+
+        if before.clone().all(|&(_, l)| l > center_low)
+            && after.clone().all(|&(_, l)| l >= center_low)
+        {
+            self.swings.push(Swing {
+                kind: SwingKind::Low,
+                index,
+                value: center_low,
+            });
+        }
+    }
+
+    /// Swings confirmed by the last kline.
+    pub fn get(&self) -> &[Swing] {
+        &self.swings
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DivergenceKind {
+    /// Lower price low with a higher indicator low, or higher price high with
+    /// a lower indicator high. The trend is running out.
+    Regular,
+    /// Higher price low with a lower indicator low, or lower price high with
+    /// a higher indicator high. The trend goes on.
+    Hidden,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Divergence {
+    pub kind: DivergenceKind,
+    pub direction: Direction,
+    /// The two swings compared.
+    pub from: Swing,
+    pub to: Swing,
+}
+
+/// Compares each price swing with the previous one of the same kind, along
+/// with the indicator values at both, if they are at most `max_distance`
+/// klines apart.
+pub struct DivergenceDetector {
+    swings: Swings,
+    max_distance: usize,
+    /// Indicator values of the last `right + 1` klines, the oldest is at the
+    /// swings being confirmed.
+    indicator: VecDeque<Option<f64>>,
+    right: usize,
+    last_high: Option<(Swing, f64)>,
+    last_low: Option<(Swing, f64)>,
+    divergences: Vec<Divergence>,
+}
+
+impl DivergenceDetector {
+    pub fn new(left: usize, right: usize, max_distance: usize) -> Self {
+        Self {
+            swings: Swings::new(left, right),
+            max_distance,
+            indicator: VecDeque::with_capacity(right + 1),
+            right,
+            last_high: None,
+            last_low: None,
+            divergences: Vec::with_capacity(2),
+        }
+    }
+
+    /// `indicator` is `None` while it warms up, swings without a value are
+    /// skipped.
+    pub fn next(&mut self, high: f64, low: f64, indicator: Option<f64>) {
+        if self.indicator.len() == self.right + 1 {
+            self.indicator.pop_front();
+        }
+        self.indicator.push_back(indicator);
+        self.swings.next(high, low);
+        self.divergences.clear();
+
+        let value = match self.indicator.front() {
+            Some(&Some(value)) => value,
+            _ => return,
+        };
+
+        for i in 0..self.swings.get().len() {
+            let swing = self.swings.get()[i];
+            let last = match swing.kind {
+                SwingKind::High => self.last_high.replace((swing, value)),
+                SwingKind::Low => self.last_low.replace((swing, value)),
+            };
+
+            if let Some((from, from_value)) = last {
+                if swing.index - from.index <= self.max_distance {
+                    self.compare(from, from_value, swing, value);
+                }
+            }
+        }
+    }
+
+    fn compare(&mut self, from: Swing, from_value: f64, to: Swing, to_value: f64) {
+        let price = to.value - from.value;
+        let indicator = to_value - from_value;
+
+        // Price and indicator must move apart, not just one of them.
+        if price * indicator >= 0f64 {
+            return;
+        }
+
+        let (kind, direction) = match (to.kind, price < 0f64) {
+            (SwingKind::Low, true) => (DivergenceKind::Regular, Direction::Bullish),
+            (SwingKind::Low, false) => (DivergenceKind::Hidden, Direction::Bullish),
+            (SwingKind::High, false) => (DivergenceKind::Regular, Direction::Bearish),
+            (SwingKind::High, true) => (DivergenceKind::Hidden, Direction::Bearish),
+        };
+
+        self.divergences.push(Divergence {
+            kind,
+            direction,
+            from,
+            to,
+        });
+    }
+
+    /// Divergences confirmed by the last kline.
+    pub fn get(&self) -> &[Divergence] {
+        &self.divergences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swing_points() {
+        let mut swings = Swings::new(2, 1);
+        let mut found = Vec::new();
+
+        for &x in [3f64, 2f64, 5f64, 4f64, 1f64, 2f64, 3f64].iter() {
+            swings.next(x, x);
+            found.extend_from_slice(swings.get());
+        }
+
+        assert_eq!(
+            found,
+            vec![
+                Swing {
+                    kind: SwingKind::High,
+                    index: 2,
+                    value: 5f64
+                },
+                Swing {
+                    kind: SwingKind::Low,
+                    index: 4,
+                    value: 1f64
+                },
+            ]
+        );
+    }
+
+    /// Two price lows or highs with the indicator values at them.
+    fn detect(prices: &[f64], indicator: &[f64]) -> Vec<Divergence> {
+        let mut detector = DivergenceDetector::new(1, 1, 10);
+        let mut found = Vec::new();
+
+        for (&price, &value) in prices.iter().zip(indicator.iter()) {
+            detector.next(price, price, Some(value));
+            found.extend_from_slice(detector.get());
+        }
+        found
+    }
+
+    #[test]
+    fn regular_and_hidden_divergences() {
+        let regular = detect(
+            &[5f64, 3f64, 4f64, 2f64, 4f64],
+            &[50f64, 30f64, 40f64, 35f64, 40f64],
+        );
+        assert_eq!(regular.len(), 1);
+        assert_eq!(regular[0].kind, DivergenceKind::Regular);
+        assert_eq!(regular[0].direction, Direction::Bullish);
+        assert_eq!((regular[0].from.index, regular[0].to.index), (1, 3));
+
+        let hidden = detect(
+            &[1f64, 3f64, 2f64, 2.5f64, 1f64],
+            &[50f64, 70f64, 60f64, 75f64, 50f64],
+        );
+        assert_eq!(hidden.len(), 1);
+        assert_eq!(hidden[0].kind, DivergenceKind::Hidden);
+        assert_eq!(hidden[0].direction, Direction::Bearish);
+
+        // Both falling is no divergence.
+        assert!(detect(
+            &[5f64, 3f64, 4f64, 2f64, 4f64],
+            &[50f64, 30f64, 40f64, 20f64, 40f64]
+        )
+        .is_empty());
+    }
+}
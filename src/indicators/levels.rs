@@ -0,0 +1,352 @@
+//! Price levels: pivot points of the previous higher timeframe kline and
+//! support and resistance zones clustered from swing points.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::divergence::{Swing, Swings};
+use crate::exchange::{Interval, Kline};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PivotMethod {
+    Classic,
+    Fibonacci,
+    Camarilla,
+}
+
+impl FromStr for PivotMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "classic" => Ok(PivotMethod::Classic),
+            "fibonacci" | "fib" => Ok(PivotMethod::Fibonacci),
+            "camarilla" => Ok(PivotMethod::Camarilla),
+            _ => Err(format!(
+                "Invalid pivot method `{}`, expected classic, fibonacci or camarilla",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PivotMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PivotMethod::Classic => "classic",
+            PivotMethod::Fibonacci => "fibonacci",
+            PivotMethod::Camarilla => "camarilla",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PivotLevels {
+    pub pivot: f64,
+    /// R1, R2, ... nearest first, four for Camarilla and three otherwise.
+    pub resistance: Vec<f64>,
+    /// S1, S2, ... nearest first.
+    pub support: Vec<f64>,
+}
+
+impl PivotMethod {
+    /// Levels for the period after the one with this high, low and close.
+    pub fn levels(&self, high: f64, low: f64, close: f64) -> PivotLevels {
+        let pivot = (high + low + close) / 3f64;
+        let range = high - low;
+
+        let (resistance, support) = match self {
+            PivotMethod::Classic => (
+                vec![
+                    2f64 * pivot - low,
+                    pivot + range,
+                    high + 2f64 * (pivot - low),
+                ],
+                vec![
+                    2f64 * pivot - high,
+                    pivot - range,
+                    low - 2f64 * (high - pivot),
+                ],
+            ),
+            PivotMethod::Fibonacci => {
+                let ratios = [0.382f64, 0.618f64, 1f64];
+                (
+                    ratios.iter().map(|r| pivot + r * range).collect(),
+                    ratios.iter().map(|r| pivot - r * range).collect(),
+                )
+            }
+            PivotMethod::Camarilla => {
+                let ratios = [1f64 / 12f64, 1f64 / 6f64, 1f64 / 4f64, 1f64 / 2f64];
+                (
+                    ratios.iter().map(|r| close + 1.1f64 * r * range).collect(),
+                    ratios.iter().map(|r| close - 1.1f64 * r * range).collect(),
+                )
+            }
+        };
+
+        PivotLevels {
+            pivot,
+            resistance,
+            support,
+        }
+    }
+}
+
+/// Pivot levels on lower timeframe klines, from the last complete `period`
+/// kline built out of them.
+pub struct PivotPoints {
+    method: PivotMethod,
+    period: Interval,
+    /// Open time, high, low and close of the period in progress.
+    current: Option<(i64, f64, f64, f64)>,
+    value: Option<PivotLevels>,
+}
+
+impl PivotPoints {
+    pub fn new(method: PivotMethod, period: Interval) -> Self {
+        Self {
+            method,
+            period,
+            current: None,
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, kline: &Kline) {
+        let open_time = self.period.open_time(kline.open_time);
+
+        self.current = match self.current {
+            Some((time, high, low, _)) if time == open_time => {
+                Some((time, high.max(kline.high), low.min(kline.low), kline.close))
+            }
+            current => {
+                if let Some((_, high, low, close)) = current {
+                    self.value = Some(self.method.levels(high, low, close));
+                }
+                Some((open_time, kline.high, kline.low, kline.close))
+            }
+        };
+    }
+
+    pub fn get(&self) -> Option<&PivotLevels> {
+        self.value.as_ref()
+    }
+}
+
+/// Price band where several swing points turned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Zone {
+    pub low: f64,
+    pub high: f64,
+    /// Number of swings in the zone.
+    pub touches: usize,
+    /// Index of the latest of them.
+    pub last_index: usize,
+}
+
+impl Zone {
+    pub fn middle(&self) -> f64 {
+        (self.low + self.high) / 2f64
+    }
+}
+
+/// Groups swings whose values are within `tolerance`, relative to the lowest
+/// one of the group, into zones ordered by price. Swings that are not finite,
+/// from a gap in the data, are left out.
+pub fn zones(swings: &[Swing], tolerance: f64) -> Vec<Zone> {
+    let mut swings: Vec<Swing> = swings
+        .iter()
+        .filter(|swing| swing.value.is_finite())
+        .copied()
+        .collect();
+    swings.sort_by(|a, b| a.value.total_cmp(&b.value));
+
+    let mut zones: Vec<Zone> = Vec::new();
+    for swing in swings {
+        match zones.last_mut() {
+            Some(zone) if swing.value <= zone.low * (1f64 + tolerance) => {
+                zone.high = swing.value;
+                zone.touches += 1;
+                zone.last_index = zone.last_index.max(swing.index);
+            }
+            _ => zones.push(Zone {
+                low: swing.value,
+                high: swing.value,
+                touches: 1,
+                last_index: swing.index,
+            }),
+        }
+    }
+
+    zones
+}
+
+/// Support and resistance zones from the last `capacity` swing highs and
+/// lows.
+pub struct SupportResistance {
+    swings: Swings,
+    capacity: usize,
+    tolerance: f64,
+    /// Zones need at least this many touches.
+    min_touches: usize,
+    history: Vec<Swing>,
+    zones: Vec<Zone>,
+}
+
+impl SupportResistance {
+    pub fn new(left: usize, right: usize, capacity: usize, tolerance: f64) -> Self {
+        Self {
+            swings: Swings::new(left, right),
+            capacity,
+            tolerance,
+            min_touches: 2,
+            history: Vec::with_capacity(capacity + 2),
+            zones: Vec::new(),
+        }
+    }
+
+    pub fn min_touches(mut self, min_touches: usize) -> Self {
+        self.min_touches = min_touches;
+        self
+    }
+
+    pub fn next(&mut self, high: f64, low: f64) {
+        self.swings.next(high, low);
+        if self.swings.get().is_empty() {
+            return;
+        }
+
+        self.history.extend_from_slice(self.swings.get());
+        if self.history.len() > self.capacity {
+            let excess = self.history.len() - self.capacity;
+            self.history.drain(..excess);
+        }
+
+        let min_touches = self.min_touches;
+        self.zones = zones(&self.history, self.tolerance)
+            .into_iter()
+            .filter(|zone| zone.touches >= min_touches)
+            .collect();
+    }
+
+    pub fn get(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// Highest zone entirely below `price`.
+    pub fn support(&self, price: f64) -> Option<Zone> {
+        self.zones
+            .iter()
+            .rev()
+            .find(|zone| zone.high < price)
+            .copied()
+    }
+
+    /// Lowest zone entirely above `price`.
+    pub fn resistance(&self, price: f64) -> Option<Zone> {
+        self.zones.iter().find(|zone| zone.low > price).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::divergence::SwingKind;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn pivot_levels() {
+        let classic = PivotMethod::Classic.levels(110f64, 90f64, 106f64);
+        assert!(close(classic.pivot, 102f64));
+        assert!(close(classic.resistance[0], 114f64));
+        assert!(close(classic.support[1], 82f64));
+        assert!(close(classic.resistance[2], 134f64));
+
+        let fibonacci = PivotMethod::Fibonacci.levels(110f64, 90f64, 106f64);
+        assert!(close(fibonacci.support[0], 102f64 - 7.64f64));
+
+        let camarilla = "camarilla"
+            .parse::<PivotMethod>()
+            .unwrap()
+            .levels(110f64, 90f64, 106f64);
+        assert_eq!(camarilla.resistance.len(), 4);
+        assert!(close(camarilla.resistance[3], 117f64));
+    }
+
+    #[test]
+    fn pivots_from_the_previous_period() {
+        let hour = 3_600_000;
+        let mut pivots = PivotPoints::new(PivotMethod::Classic, Interval::Day(1));
+
+        for (i, &(high, low)) in [(105f64, 95f64), (110f64, 100f64)].iter().enumerate() {
+            pivots.next(&Kline {
+                open_time: i as i64 * hour,
+                high,
+                low,
+                close: 100f64,
+                ..Kline::default()
+            });
+        }
+        assert_eq!(pivots.get(), None);
+
+        pivots.next(&Kline {
+            open_time: 24 * hour,
+            high: 200f64,
+            low: 150f64,
+            close: 180f64,
+            ..Kline::default()
+        });
+        // High 110, low 95 and close 100 of the first day.
+        assert!(close(pivots.get().unwrap().pivot, 305f64 / 3f64));
+    }
+
+    #[test]
+    fn swing_zones() {
+        let swing = |index, value| Swing {
+            kind: SwingKind::Low,
+            index,
+            value,
+        };
+        let found = zones(
+            &[swing(1, 100f64), swing(5, 120f64), swing(9, 100.5f64)],
+            0.01f64,
+        );
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[0].touches, found[0].last_index), (2, 9));
+        assert_eq!(found[0].high, 100.5f64);
+
+        let found = zones(
+            &[
+                swing(1, f64::NAN),
+                swing(2, 100f64),
+                swing(3, f64::INFINITY),
+            ],
+            0.01f64,
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].low, found[0].last_index), (100f64, 2));
+
+        let mut levels = SupportResistance::new(1, 1, 20, 0.01f64);
+        for &(high, low) in [
+            (102f64, 100f64),
+            (101f64, 95f64),
+            (110f64, 100f64),
+            (103f64, 95.5f64),
+            (109.5f64, 101f64),
+            (105f64, 102f64),
+        ]
+        .iter()
+        {
+            levels.next(high, low);
+        }
+        // Lows at 95 and 95.5, highs at 110 and 109.5.
+        assert_eq!(levels.get().len(), 2);
+        assert_eq!(levels.support(104f64).unwrap().low, 95f64);
+        assert_eq!(levels.resistance(104f64).unwrap().high, 110f64);
+        assert_eq!(levels.resistance(111f64), None);
+    }
+}
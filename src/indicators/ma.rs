@@ -0,0 +1,516 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Dema, Ema, Sma};
+
+/// Linearly weighted moving average, the newest value weighs `period`.
+pub struct Wma {
+    period: usize,
+    data: Box<[f64]>,
+    index: usize,
+    count: usize,
+    sum: f64,
+    weighted: f64,
+    value: Option<f64>,
+}
+
+impl Wma {
+    pub fn new(period: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+
+        Self {
+            period,
+            data: vec![0f64; period].into_boxed_slice(),
+            index: 0,
+            count: 0,
+            sum: 0f64,
+            weighted: 0f64,
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, source: f64) {
+        let old = self.data[self.index];
+        self.data[self.index] = source;
+        self.index = (self.index + 1) % self.period;
+
+        if self.count < self.period {
+            self.count += 1;
+            self.weighted += self.count as f64 * source;
+            self.sum += source;
+        } else {
+            // Every value loses one weight, the new one comes in at `period`.
+            self.weighted += self.period as f64 * source - self.sum;
+            self.sum += source - old;
+        }
+
+        // Re-sum once per window, when the oldest value is back at the start
+        // of the buffer, so rounding errors of the running sums cannot pile
+        // up over long runs.
+        if self.index == 0 {
+            self.sum = self.data.iter().sum();
+            self.weighted = (1..=self.period)
+                .zip(self.data.iter())
+                .map(|(weight, x)| weight as f64 * x)
+                .sum();
+        }
+
+        if self.count == self.period {
+            let n = self.period as f64;
+            self.value = Some(self.weighted / (n * (n + 1f64) / 2f64));
+        }
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Hull moving average, a WMA of `2 * WMA(n / 2) - WMA(n)` over `sqrt(n)`.
+pub struct Hma {
+    half: Wma,
+    full: Wma,
+    hull: Wma,
+}
+
+impl Hma {
+    pub fn new(period: usize) -> Self {
+        Self {
+            half: Wma::new((period / 2).max(1)),
+            full: Wma::new(period),
+            hull: Wma::new(((period as f64).sqrt().round() as usize).max(1)),
+        }
+    }
+
+    pub fn next(&mut self, source: f64) {
+        self.half.next(source);
+        self.full.next(source);
+
+        if let (Some(half), Some(full)) = (self.half.get(), self.full.get()) {
+            self.hull.next(2f64 * half - full);
+        }
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.hull.get()
+    }
+}
+
+/// Triple exponential moving average, `3 * EMA - 3 * EMA(EMA) + EMA(EMA(EMA))`.
+pub struct Tema {
+    first: Ema,
+    second: Ema,
+    third: Ema,
+}
+
+impl Tema {
+    pub fn new(period: usize) -> Self {
+        Self {
+            first: Ema::new(period),
+            second: Ema::new(period),
+            third: Ema::new(period),
+        }
+    }
+
+    pub fn next(&mut self, source: f64) {
+        self.first.next(source);
+
+        if let Some(first) = self.first.get() {
+            self.second.next(first);
+
+            if let Some(second) = self.second.get() {
+                self.third.next(second);
+            }
+        }
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        match (self.first.get(), self.second.get(), self.third.get()) {
+            (Some(first), Some(second), Some(third)) => Some(3f64 * first - 3f64 * second + third),
+            _ => None,
+        }
+    }
+}
+
+/// Kaufman's adaptive moving average. Follows the price with the `fast`
+/// EMA constant in a clean trend and barely moves with the `slow` one in
+/// noise, judged by the efficiency ratio over `period` changes.
+pub struct Kama {
+    period: usize,
+    fast: f64,
+    slow: f64,
+    data: Box<[f64]>,
+    index: usize,
+    count: usize,
+    volatility: f64,
+    value: Option<f64>,
+}
+
+impl Kama {
+    pub fn new(period: usize, fast: usize, slow: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+
+        Self {
+            period,
+            fast: 2f64 / (fast + 1) as f64,
+            slow: 2f64 / (slow + 1) as f64,
+            data: vec![0f64; period + 1].into_boxed_slice(),
+            index: 0,
+            count: 0,
+            volatility: 0f64,
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, source: f64) {
+        let len = self.data.len();
+        let prev = self.data[(self.index + len - 1) % len];
+        // The change leaving the window once it is full.
+        let oldest_change = (self.data[(self.index + 1) % len] - self.data[self.index]).abs();
+
+        self.data[self.index] = source;
+        self.index = (self.index + 1) % len;
+
+        if self.count == 0 {
+            self.count = 1;
+            return;
+        }
+
+        self.volatility += (source - prev).abs();
+        if self.count <= self.period {
+            self.count += 1;
+        } else {
+            self.volatility -= oldest_change;
+        }
+
+        // Re-sum once per window like `Wma`, the buffer then runs from the
+        // oldest value to the newest.
+        if self.index == 0 {
+            self.volatility = self.data.windows(2).map(|w| (w[1] - w[0]).abs()).sum();
+        }
+
+        if self.count <= self.period {
+            return;
+        }
+
+        // The value `period` klines ago sits right after the newest one.
+        let change = (source - self.data[self.index]).abs();
+        let er = if self.volatility > 0f64 {
+            (change / self.volatility).min(1f64)
+        } else {
+            0f64
+        };
+        let sc = (er * (self.fast - self.slow) + self.slow).powi(2);
+        let kama = self.value.unwrap_or(prev);
+
+        self.value = Some(kama + sc * (source - kama));
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Volume weighted moving average.
+pub struct Vwma {
+    period: usize,
+    data: Box<[(f64, f64)]>,
+    index: usize,
+    count: usize,
+    volume: f64,
+    weighted: f64,
+    value: Option<f64>,
+}
+
+impl Vwma {
+    pub fn new(period: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+
+        Self {
+            period,
+            data: vec![(0f64, 0f64); period].into_boxed_slice(),
+            index: 0,
+            count: 0,
+            volume: 0f64,
+            weighted: 0f64,
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, source: f64, volume: f64) {
+        let (old, old_volume) = self.data[self.index];
+        self.data[self.index] = (source, volume);
+        self.index = (self.index + 1) % self.period;
+
+        self.weighted += source * volume - old * old_volume;
+        self.volume += volume - old_volume;
+        self.count = (self.count + 1).min(self.period);
+
+        // Re-sum once per window like `Wma`.
+        if self.index == 0 {
+            self.weighted = self.data.iter().map(|(x, volume)| x * volume).sum();
+            self.volume = self.data.iter().map(|(_, volume)| volume).sum();
+        }
+
+        if self.count == self.period && self.volume > 0f64 {
+            self.value = Some(self.weighted / self.volume);
+        }
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Smoothed (Wilder's) moving average, an EMA with `alpha = 1 / period`.
+pub struct Smma {
+    inner: Ema,
+}
+
+impl Smma {
+    pub fn new(period: usize) -> Self {
+        Self {
+            inner: Ema::with_constant(period, 1f64 / period as f64),
+        }
+    }
+
+    pub fn next(&mut self, source: f64) {
+        self.inner.next(source);
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.inner.get()
+    }
+}
+
+/// Moving average types by name, for configuration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaType {
+    Sma,
+    Ema,
+    Dema,
+    Tema,
+    Wma,
+    Hma,
+    Kama,
+    Smma,
+    Vwma,
+}
+
+impl MaType {
+    pub fn build(&self, period: usize) -> MovingAverage {
+        match self {
+            MaType::Sma => MovingAverage::Sma(Sma::new(period)),
+            MaType::Ema => MovingAverage::Ema(Ema::new(period)),
+            MaType::Dema => MovingAverage::Dema(Dema::new(period)),
+            MaType::Tema => MovingAverage::Tema(Tema::new(period)),
+            MaType::Wma => MovingAverage::Wma(Wma::new(period)),
+            MaType::Hma => MovingAverage::Hma(Hma::new(period)),
+            MaType::Kama => MovingAverage::Kama(Kama::new(period, 2, 30)),
+            MaType::Smma => MovingAverage::Smma(Smma::new(period)),
+            MaType::Vwma => MovingAverage::Vwma(Vwma::new(period)),
+        }
+    }
+}
+
+impl FromStr for MaType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sma" => Ok(MaType::Sma),
+            "ema" => Ok(MaType::Ema),
+            "dema" => Ok(MaType::Dema),
+            "tema" => Ok(MaType::Tema),
+            "wma" => Ok(MaType::Wma),
+            "hma" => Ok(MaType::Hma),
+            "kama" => Ok(MaType::Kama),
+            "smma" | "rma" => Ok(MaType::Smma),
+            "vwma" => Ok(MaType::Vwma),
+            _ => Err(format!(
+                "Invalid moving average `{}`, expected sma, ema, dema, tema, wma, hma, kama, smma or vwma",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MaType::Sma => "SMA",
+            MaType::Ema => "EMA",
+            MaType::Dema => "DEMA",
+            MaType::Tema => "TEMA",
+            MaType::Wma => "WMA",
+            MaType::Hma => "HMA",
+            MaType::Kama => "KAMA",
+            MaType::Smma => "SMMA",
+            MaType::Vwma => "VWMA",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Any of the moving averages, chosen at runtime with [`MaType::build`].
+pub enum MovingAverage {
+    Sma(Sma),
+    Ema(Ema),
+    Dema(Dema),
+    Tema(Tema),
+    Wma(Wma),
+    Hma(Hma),
+    Kama(Kama),
+    Smma(Smma),
+    Vwma(Vwma),
+}
+
+impl MovingAverage {
+    /// `volume` is only used by the VWMA.
+    pub fn next(&mut self, source: f64, volume: f64) {
+        match self {
+            MovingAverage::Sma(ma) => ma.next(source),
+            MovingAverage::Ema(ma) => ma.next(source),
+            MovingAverage::Dema(ma) => ma.next(source),
+            MovingAverage::Tema(ma) => ma.next(source),
+            MovingAverage::Wma(ma) => ma.next(source),
+            MovingAverage::Hma(ma) => ma.next(source),
+            MovingAverage::Kama(ma) => ma.next(source),
+            MovingAverage::Smma(ma) => ma.next(source),
+            MovingAverage::Vwma(ma) => ma.next(source, volume),
+        }
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        match self {
+            MovingAverage::Sma(ma) => ma.get(),
+            MovingAverage::Ema(ma) => ma.get(),
+            MovingAverage::Dema(ma) => ma.get(),
+            MovingAverage::Tema(ma) => ma.get(),
+            MovingAverage::Wma(ma) => ma.get(),
+            MovingAverage::Hma(ma) => ma.get(),
+            MovingAverage::Kama(ma) => ma.get(),
+            MovingAverage::Smma(ma) => ma.get(),
+            MovingAverage::Vwma(ma) => ma.get(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_averages() {
+        let mut wma = Wma::new(3);
+        let mut vwma = Vwma::new(2);
+
+        for (i, &x) in [1f64, 2f64, 3f64, 4f64, 10f64].iter().enumerate() {
+            wma.next(x);
+            vwma.next(x, i as f64 + 1f64);
+        }
+
+        // (3 * 1 + 4 * 2 + 10 * 3) / 6 and (4 * 4 + 10 * 5) / 9
+        assert!((wma.get().unwrap() - 41f64 / 6f64).abs() < 1e-12);
+        assert!((vwma.get().unwrap() - 66f64 / 9f64).abs() < 1e-12);
+
+        // The running sums lose the ones next to 1e16, re-summing recovers.
+        let mut wma = Wma::new(3);
+        wma.next(1e16);
+        for _ in 0..8 {
+            wma.next(1f64);
+        }
+        assert_eq!(wma.get(), Some(1f64));
+    }
+
+    /// Long series with a spike now and then, which the running sums of
+    /// the windowed averages cannot carry without losing the small values.
+    fn spiky_series() -> Vec<(f64, f64)> {
+        (0..10_000)
+            .map(|i| {
+                let x = 100f64 + (i * 7919 % 101) as f64 / 100f64;
+                let volume = 1f64 + (i * 104_729 % 13) as f64;
+                if i % 1000 == 500 {
+                    (1e9 * x, 1e6 * volume)
+                } else {
+                    (x, volume)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn windowed_sums_on_a_long_series() {
+        // Once the spike left the window the running sums are off by more
+        // than the small values in it. At the end of each window they are
+        // re-summed in window order and match a direct recomputation.
+        let series = spiky_series();
+        let period = 10;
+        let mut vwma = Vwma::new(period);
+        let mut kama = Kama::new(period, 2, 30);
+
+        for (i, &(x, volume)) in series.iter().enumerate() {
+            vwma.next(x, volume);
+            kama.next(x);
+
+            if (i + 1) % period == 0 {
+                let window = &series[i + 1 - period..=i];
+                let weighted: f64 = window.iter().map(|(x, volume)| x * volume).sum();
+                let volume: f64 = window.iter().map(|(_, volume)| volume).sum();
+                assert_eq!(vwma.get(), Some(weighted / volume), "vwma[{}]", i);
+            }
+
+            if (i + 1) % (period + 1) == 0 {
+                let volatility: f64 = series[i - period..=i]
+                    .windows(2)
+                    .map(|w| (w[1].0 - w[0].0).abs())
+                    .sum();
+                assert_eq!(kama.volatility, volatility, "kama[{}]", i);
+            }
+        }
+    }
+
+    #[test]
+    fn trend_followers_on_a_line() {
+        // Hull and triple EMAs cancel the lag of a straight line. KAMA moves
+        // with the fast constant squared when the efficiency ratio is one,
+        // trailing by (1 - 4 / 9) / (4 / 9) = 1.25.
+        let mut hma = Hma::new(9);
+        let mut tema = Tema::new(5);
+        let mut kama = Kama::new(10, 2, 30);
+
+        for i in 0..60 {
+            let x = i as f64;
+            hma.next(x);
+            tema.next(x);
+            kama.next(x);
+        }
+
+        assert!((hma.get().unwrap() - 59f64).abs() < 1e-9);
+        assert!((tema.get().unwrap() - 59f64).abs() < 1e-6);
+        assert!((kama.get().unwrap() - 57.75f64).abs() < 1e-6);
+
+        let mut flat = Kama::new(10, 2, 30);
+        for i in 0..30 {
+            flat.next(if i % 2 == 0 { 1f64 } else { 2f64 });
+        }
+        let value = flat.get().unwrap();
+        assert!(value > 1f64 && value < 2f64);
+    }
+
+    #[test]
+    fn moving_average_by_name() {
+        assert_eq!("HMA".parse::<MaType>(), Ok(MaType::Hma));
+        assert_eq!("rma".parse::<MaType>(), Ok(MaType::Smma));
+        assert!("lsma".parse::<MaType>().is_err());
+
+        let mut sma = MaType::Sma.build(3);
+        let mut smma = MaType::Smma.build(3);
+        for &x in [1f64, 2f64, 12f64].iter() {
+            sma.next(x, 0f64);
+            smma.next(x, 0f64);
+        }
+        assert_eq!(sma.get(), Some(5f64));
+        assert_eq!(smma.get(), Some(5f64));
+    }
+}
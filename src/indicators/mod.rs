@@ -1,37 +1,58 @@
-pub struct Sma<const N: usize> {
-    data: [f64; N],
+pub mod batch;
+pub mod divergence;
+pub mod levels;
+pub mod ma;
+pub mod oscillators;
+pub mod patterns;
+#[cfg(test)]
+mod reference;
+mod td;
+pub mod trend;
+
+pub use td::{Countdown, TdSeq, TdSeqValue};
+
+use std::collections::VecDeque;
+
+pub struct Sma {
+    period: usize,
+    data: Box<[f64]>,
     index: usize,
+    count: usize,
+    sum: f64,
     value: Option<f64>,
 }
 
-impl<const N: usize> Sma<N> {
-    pub fn new() -> Self {
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+
         Self {
-            data: [f64::NAN; N],
+            period,
+            data: vec![0f64; period].into_boxed_slice(),
             index: 0,
+            count: 0,
+            sum: 0f64,
             value: None,
         }
     }
 
     pub fn next(&mut self, source: f64) {
-        if self.data[self.index].is_nan() {
-            self.data[self.index] = source;
+        self.sum += source - self.data[self.index];
+        self.data[self.index] = source;
+        self.index = (self.index + 1) % self.period;
 
-            if self.index < N - 1 {
-                self.index += 1;
-            } else {
-                self.value = Some(self.data.iter().sum::<f64>() / N as f64);
-                self.index = 0;
-            }
-        } else {
-            self.data[self.index] = source;
-            self.value = Some(self.data.iter().sum::<f64>() / N as f64);
+        // Re-sum once per window so rounding errors of the running sum
+        // cannot pile up over long runs.
+        if self.index == 0 {
+            self.sum = self.data.iter().sum();
+        }
 
-            if self.index < N - 1 {
-                self.index += 1;
-            } else {
-                self.index = 0;
-            }
+        if self.count < self.period {
+            self.count += 1;
+        }
+
+        if self.count == self.period {
+            self.value = Some(self.sum / self.period as f64);
         }
     }
 
@@ -40,47 +61,59 @@ impl<const N: usize> Sma<N> {
     }
 }
 
-pub struct StandardDeviation<const N: usize> {
-    data: [f64; N],
+/// Population standard deviation of the last `period` values, updated with
+/// Welford's algorithm.
+pub struct StandardDeviation {
+    period: usize,
+    data: Box<[f64]>,
     index: usize,
+    count: usize,
+    mean: f64,
+    m2: f64,
     value: Option<f64>,
 }
 
-impl<const N: usize> StandardDeviation<N> {
-    pub fn new() -> Self {
+impl StandardDeviation {
+    pub fn new(period: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+
         Self {
-            data: [f64::NAN; N],
+            period,
+            data: vec![0f64; period].into_boxed_slice(),
             index: 0,
+            count: 0,
+            mean: 0f64,
+            m2: 0f64,
             value: None,
         }
     }
 
     pub fn next(&mut self, source: f64) {
-        if self.data[self.index].is_nan() {
-            self.data[self.index] = source;
+        let old = self.data[self.index];
+        self.data[self.index] = source;
+        self.index = (self.index + 1) % self.period;
 
-            if self.index < N - 1 {
-                self.index += 1;
-            } else {
-                let mean = self.data.iter().sum::<f64>() / N as f64;
-                self.value = Some(
-                    (self.data.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / N as f64).sqrt(),
-                );
+        if self.count < self.period {
+            self.count += 1;
 
-                self.index = 0;
-            }
+            let delta = source - self.mean;
+            self.mean += delta / self.count as f64;
+            self.m2 += delta * (source - self.mean);
         } else {
-            self.data[self.index] = source;
+            let mean = self.mean;
+            self.mean += (source - old) / self.period as f64;
+            self.m2 += (source - old) * (source - self.mean + old - mean);
+        }
 
-            let mean = self.data.iter().sum::<f64>() / N as f64;
-            self.value =
-                Some((self.data.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / N as f64).sqrt());
+        // Same as `Sma`, start over from the window once per period.
+        if self.index == 0 {
+            let n = self.period as f64;
+            self.mean = self.data.iter().sum::<f64>() / n;
+            self.m2 = self.data.iter().map(|v| (v - self.mean).powi(2)).sum();
+        }
 
-            if self.index < N - 1 {
-                self.index += 1;
-            } else {
-                self.index = 0;
-            }
+        if self.count == self.period {
+            self.value = Some((self.m2.max(0f64) / self.period as f64).sqrt());
         }
     }
 
@@ -352,20 +385,24 @@ impl Rsi {
     }
 }
 
-pub struct StochRsi<const N: usize> {
+pub struct StochRsi {
+    period: usize,
+    count: usize,
     rsi: Rsi,
     maximum: Maximum,
     minimum: Minimum,
-    value: Sma<N>,
+    value: Sma,
 }
 
-impl<const N: usize> StochRsi<N> {
-    pub fn new() -> Self {
+impl StochRsi {
+    pub fn new(period: usize) -> Self {
         Self {
-            rsi: Rsi::new(N),
-            maximum: Maximum::new(N),
-            minimum: Minimum::new(N),
-            value: Sma::<N>::new(),
+            period,
+            count: 0,
+            rsi: Rsi::new(period),
+            maximum: Maximum::new(period),
+            minimum: Minimum::new(period),
+            value: Sma::new(period),
         }
     }
 
@@ -374,9 +411,21 @@ impl<const N: usize> StochRsi<N> {
         if let Some(rsi) = self.rsi.get() {
             self.maximum.next(rsi);
             self.minimum.next(rsi);
+            self.count += 1;
+
+            // Wait for a full window of RSI values, and read a flat one as
+            // the bottom of the range instead of dividing by zero.
+            if self.count < self.period {
+                return;
+            }
 
             if let (Some(max), Some(min)) = (self.maximum.get(), self.minimum.get()) {
-                self.value.next(100f64 * (rsi - min) / (max - min));
+                let stoch = if max > min {
+                    100f64 * (rsi - min) / (max - min)
+                } else {
+                    0f64
+                };
+                self.value.next(stoch);
             }
         }
     }
@@ -386,122 +435,90 @@ impl<const N: usize> StochRsi<N> {
     }
 }
 
+/// Highest of the last `period` values, kept in a monotonic deque.
 pub struct Maximum {
     period: usize,
-    max_index: usize,
-    cur_index: usize,
-    values: Box<[f64]>,
+    tick: usize,
+    values: VecDeque<(usize, f64)>,
 }
 
 impl Maximum {
     pub fn new(period: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+
         Self {
             period,
-            max_index: 0,
-            cur_index: 0,
-            values: vec![f64::MIN; period].into_boxed_slice(),
+            tick: 0,
+            values: VecDeque::with_capacity(period),
         }
     }
 
-    fn find_max_index(&self) -> usize {
-        let mut max = f64::MIN;
-        let mut index: usize = 0;
-
-        for (i, &val) in self.values.iter().enumerate() {
-            if max < val {
-                max = val;
-                index = i;
-            }
-        }
-
-        index
-    }
-
     pub fn next(&mut self, price: f64) {
-        self.values[self.cur_index] = price;
+        while matches!(self.values.back(), Some(&(_, v)) if v <= price) {
+            self.values.pop_back();
+        }
+        self.values.push_back((self.tick, price));
 
-        if price > self.values[self.max_index] {
-            self.max_index = self.cur_index;
-        } else if self.max_index == self.cur_index {
-            self.max_index = self.find_max_index();
+        while matches!(self.values.front(), Some(&(t, _)) if t + self.period <= self.tick) {
+            self.values.pop_front();
         }
 
-        self.cur_index = if self.cur_index + 1 < self.period {
-            self.cur_index + 1
-        } else {
-            0
-        };
+        self.tick += 1;
     }
 
     pub fn get(&self) -> Option<f64> {
-        self.values.get(self.max_index).copied()
+        self.values.front().map(|&(_, v)| v)
     }
 }
 
+/// Lowest of the last `period` values, kept in a monotonic deque.
 pub struct Minimum {
     period: usize,
-    min_index: usize,
-    cur_index: usize,
-    values: Box<[f64]>,
+    tick: usize,
+    values: VecDeque<(usize, f64)>,
 }
 
 impl Minimum {
     pub fn new(period: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+
         Self {
             period,
-            min_index: 0,
-            cur_index: 0,
-            values: vec![f64::MAX; period].into_boxed_slice(),
-        }
-    }
-
-    fn find_max_index(&self) -> usize {
-        let mut min = f64::MAX;
-        let mut index: usize = 0;
-
-        for (i, &val) in self.values.iter().enumerate() {
-            if min > val {
-                min = val;
-                index = i;
-            }
+            tick: 0,
+            values: VecDeque::with_capacity(period),
         }
-
-        index
     }
 
     pub fn next(&mut self, price: f64) {
-        self.values[self.cur_index] = price;
+        while matches!(self.values.back(), Some(&(_, v)) if v >= price) {
+            self.values.pop_back();
+        }
+        self.values.push_back((self.tick, price));
 
-        if price < self.values[self.min_index] {
-            self.min_index = self.cur_index;
-        } else if self.min_index == self.cur_index {
-            self.min_index = self.find_max_index();
+        while matches!(self.values.front(), Some(&(t, _)) if t + self.period <= self.tick) {
+            self.values.pop_front();
         }
 
-        self.cur_index = if self.cur_index + 1 < self.period {
-            self.cur_index + 1
-        } else {
-            0
-        };
+        self.tick += 1;
     }
 
     pub fn get(&self) -> Option<f64> {
-        self.values.get(self.min_index).copied()
+        self.values.front().map(|&(_, v)| v)
     }
 }
 
-pub struct BollingerBand<const N: usize> {
-    typical_price: Sma<N>,
-    dev: StandardDeviation<N>,
+pub struct BollingerBand {
+    typical_price: Sma,
+    dev: StandardDeviation,
     m: f64,
     value: Option<(f64, f64, f64)>,
 }
 
-impl<const N: usize> BollingerBand<N> {
-    pub fn new(m: f64) -> Self {
+impl BollingerBand {
+    pub fn new(period: usize, m: f64) -> Self {
         Self {
-            typical_price: Sma::<N>::new(),
-            dev: StandardDeviation::<N>::new(),
+            typical_price: Sma::new(period),
+            dev: StandardDeviation::new(period),
             m,
             value: None,
         }
@@ -532,100 +549,68 @@ impl<const N: usize> BollingerBand<N> {
     }
 }
 
-pub struct TdSeq {
-    highs: [f64; 5],
-    lows: [f64; 5],
-    closes: [f64; 5],
-    index: usize,
-    pub setup_count: i32,
-    perfect: bool,
-}
-
-impl TdSeq {
-    pub fn new() -> Self {
-        Self {
-            highs: [f64::NAN; 5],
-            lows: [f64::NAN; 5],
-            closes: [f64::NAN; 5],
-            index: 0,
-            setup_count: 0,
-            perfect: false,
-        }
-    }
-
-    pub fn next(&mut self, high: f64, low: f64, close: f64) {
-        if self.closes[self.index].is_nan() && self.index != 4 {
-            self.closes[self.index] = close;
-            self.highs[self.index] = high;
-            self.lows[self.index] = low;
-            self.index += 1;
-        } else {
-            self.closes[self.index] = close;
-            self.highs[self.index] = high;
-            self.lows[self.index] = low;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
 
-            let prev_index = if let Some(prev_index) = self.index.checked_sub(4) {
-                prev_index
-            } else {
-                self.index + 1
-            };
+    /// Random walk with flat stretches, so windows contain ties.
+    fn prices(n: usize) -> Vec<f64> {
+        let mut rng = Rng::new(34);
+        let mut price = 100f64;
 
-            if self.closes[prev_index] < self.closes[self.index] {
-                if self.setup_count.is_positive() || self.setup_count == -9 {
-                    self.setup_count = -1;
-                } else {
-                    self.setup_count -= 1;
+        (0..n)
+            .map(|_| {
+                if rng.below(4) > 0 {
+                    price += rng.next_f64() * 2f64 - 1f64;
                 }
-            } else {
-                if self.setup_count.is_negative() || self.setup_count == 9 {
-                    self.setup_count = 1;
-                } else {
-                    self.setup_count += 1;
-                }
-            }
-
-            if self.setup_count.abs() == 9 {
-                self.perfect = if self.setup_count.is_positive() {
-                    let sixth = self.lows[(prev_index + 1) % 5];
-                    let seventh = self.lows[(prev_index + 2) % 5];
-                    let eighth = self.lows[(prev_index + 3) % 5];
-                    let ninth = self.lows[self.index];
-
-                    (eighth < sixth && eighth < seventh) || (ninth < sixth && ninth < seventh)
-                } else {
-                    let sixth = self.highs[(prev_index + 1) % 5];
-                    let seventh = self.highs[(prev_index + 2) % 5];
-                    let eighth = self.highs[(prev_index + 3) % 5];
-                    let ninth = self.highs[self.index];
-
-                    (eighth > sixth && eighth > seventh) || (ninth > sixth && ninth > seventh)
-                };
-            }
-
-            if self.index == 4 {
-                self.index = 0;
-            } else {
-                self.index += 1;
-            }
-        }
+                price
+            })
+            .collect()
     }
 
-    pub fn buy_perfect(&self) -> bool {
-        self.perfect && self.setup_count == 9
+    fn assert_close(a: Option<f64>, b: Option<f64>) {
+        match (a, b) {
+            (Some(a), Some(b)) => assert!((a - b).abs() < 1e-9, "{} != {}", a, b),
+            _ => assert_eq!(a, b),
+        }
     }
 
-    pub fn sell_perfect(&self) -> bool {
-        self.perfect && self.setup_count == -9
+    #[test]
+    fn rolling_matches_full_window() {
+        let prices = prices(5000);
+
+        for &period in [1, 3, 20, 64].iter() {
+            let mut sma = Sma::new(period);
+            let mut std = StandardDeviation::new(period);
+            let mut max = Maximum::new(period);
+            let mut min = Minimum::new(period);
+
+            for i in 0..prices.len() {
+                sma.next(prices[i]);
+                std.next(prices[i]);
+                max.next(prices[i]);
+                min.next(prices[i]);
+
+                let window = &prices[(i + 1).saturating_sub(period)..=i];
+                let n = period as f64;
+                let full = window.len() == period;
+                let mean = window.iter().sum::<f64>() / n;
+                let dev = (window.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+
+                assert_close(sma.get(), Some(mean).filter(|_| full));
+                // Compare variances, the square root magnifies the rounding
+                // error of the running sums around zero deviation.
+                assert_close(std.get().map(|d| d * d), Some(dev * dev).filter(|_| full));
+                assert_eq!(max.get(), window.iter().copied().reduce(f64::max));
+                assert_eq!(min.get(), window.iter().copied().reduce(f64::min));
+            }
+        }
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     #[test]
     fn sma_test() {
-        let mut sma = Sma::<3>::new();
+        let mut sma = Sma::new(3);
 
         assert_eq!(None, sma.get());
         sma.next(1f64);
@@ -640,7 +625,7 @@ mod tests {
 
     #[test]
     fn std_dev_test() {
-        let mut std = StandardDeviation::<8>::new();
+        let mut std = StandardDeviation::new(8);
 
         assert_eq!(None, std.get());
         std.next(2f64);
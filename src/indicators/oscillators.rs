@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+
+use super::trend::Donchian;
+use super::Sma;
+
+/// Stochastic oscillator. %K is the close in the range of the last `period`
+/// klines smoothed over `k_smoothing`, 1 for the fast stochastic, and %D the
+/// SMA of %K over `d_period`.
+pub struct Stochastic {
+    range: Donchian,
+    k: Sma,
+    d: Sma,
+}
+
+impl Default for Stochastic {
+    fn default() -> Self {
+        Self::new(14, 3, 3)
+    }
+}
+
+impl Stochastic {
+    pub fn new(period: usize, k_smoothing: usize, d_period: usize) -> Self {
+        Self {
+            range: Donchian::new(period),
+            k: Sma::new(k_smoothing),
+            d: Sma::new(d_period),
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64, close: f64) {
+        self.range.next(high, low);
+
+        // A flat range reads as its bottom.
+        if let Some((high, _, low)) = self.range.get() {
+            self.k.next(if high > low {
+                100f64 * (close - low) / (high - low)
+            } else {
+                0f64
+            });
+
+            if let Some(k) = self.k.get() {
+                self.d.next(k);
+            }
+        }
+    }
+
+    /// `(%K, %D)`
+    pub fn get(&self) -> (Option<f64>, Option<f64>) {
+        (self.k.get(), self.d.get())
+    }
+}
+
+/// Williams %R, the close in the range of the last `period` klines from -100
+/// at the low to 0 at the high.
+pub struct WilliamsR {
+    range: Donchian,
+    value: Option<f64>,
+}
+
+impl WilliamsR {
+    pub fn new(period: usize) -> Self {
+        Self {
+            range: Donchian::new(period),
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64, close: f64) {
+        self.range.next(high, low);
+
+        if let Some((high, _, low)) = self.range.get() {
+            self.value = Some(if high > low {
+                -100f64 * (high - close) / (high - low)
+            } else {
+                -100f64
+            });
+        }
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Commodity Channel Index, the distance of the typical price from its SMA
+/// in units of 0.015 mean absolute deviations.
+pub struct Cci {
+    period: usize,
+    prices: VecDeque<f64>,
+    sma: Sma,
+    value: Option<f64>,
+}
+
+impl Cci {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            prices: VecDeque::with_capacity(period + 1),
+            sma: Sma::new(period),
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64, close: f64) {
+        let typical = (high + low + close) / 3f64;
+
+        self.prices.push_back(typical);
+        if self.prices.len() > self.period {
+            self.prices.pop_front();
+        }
+        self.sma.next(typical);
+
+        if let Some(mean) = self.sma.get() {
+            // The mean deviation has no running form, it takes a pass over
+            // the window.
+            let deviation =
+                self.prices.iter().map(|p| (p - mean).abs()).sum::<f64>() / self.period as f64;
+
+            self.value = Some(if deviation > 0f64 {
+                (typical - mean) / (0.015f64 * deviation)
+            } else {
+                0f64
+            });
+        }
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Change of the close over the last `period` klines.
+pub struct Roc {
+    period: usize,
+    closes: VecDeque<f64>,
+}
+
+impl Roc {
+    pub fn new(period: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+
+        Self {
+            period,
+            closes: VecDeque::with_capacity(period + 1),
+        }
+    }
+
+    pub fn next(&mut self, close: f64) {
+        self.closes.push_back(close);
+        if self.closes.len() > self.period + 1 {
+            self.closes.pop_front();
+        }
+    }
+
+    /// Close now and `period` klines ago.
+    fn ends(&self) -> Option<(f64, f64)> {
+        if self.closes.len() <= self.period {
+            return None;
+        }
+
+        Some((*self.closes.back()?, *self.closes.front()?))
+    }
+
+    /// Rate of change in percent.
+    pub fn get(&self) -> Option<f64> {
+        self.ends()
+            .map(|(close, close_n)| 100f64 * (close - close_n) / close_n)
+    }
+
+    /// Momentum, the change in price.
+    pub fn momentum(&self) -> Option<f64> {
+        self.ends().map(|(close, close_n)| close - close_n)
+    }
+}
+
+/// Bill Williams' Awesome Oscillator, the fast SMA of the median price less
+/// the slow one.
+pub struct AwesomeOscillator {
+    fast: Sma,
+    slow: Sma,
+    value: Option<f64>,
+}
+
+impl Default for AwesomeOscillator {
+    fn default() -> Self {
+        Self::new(5, 34)
+    }
+}
+
+impl AwesomeOscillator {
+    pub fn new(fast: usize, slow: usize) -> Self {
+        Self {
+            fast: Sma::new(fast),
+            slow: Sma::new(slow),
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64) {
+        let median = (high + low) / 2f64;
+        self.fast.next(median);
+        self.slow.next(median);
+
+        if let (Some(fast), Some(slow)) = (self.fast.get(), self.slow.get()) {
+            self.value = Some(fast - slow);
+        }
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_oscillators() {
+        let mut stochastic = Stochastic::new(3, 1, 2);
+        let mut williams_r = WilliamsR::new(3);
+
+        for &(high, low, close) in [(10f64, 8f64, 9f64), (12f64, 9f64, 11f64)].iter() {
+            stochastic.next(high, low, close);
+            williams_r.next(high, low, close);
+        }
+        assert_eq!(stochastic.get(), (None, None));
+        assert_eq!(williams_r.get(), None);
+
+        // Ranges 8 to 13 and 9 to 13.
+        stochastic.next(13f64, 10f64, 12f64);
+        williams_r.next(13f64, 10f64, 12f64);
+        assert_eq!(stochastic.get(), (Some(80f64), None));
+        assert_eq!(williams_r.get(), Some(-20f64));
+
+        stochastic.next(11f64, 10f64, 10f64);
+        assert_eq!(stochastic.get(), (Some(25f64), Some(52.5f64)));
+
+        let mut flat = WilliamsR::new(2);
+        flat.next(5f64, 5f64, 5f64);
+        flat.next(5f64, 5f64, 5f64);
+        assert_eq!(flat.get(), Some(-100f64));
+    }
+
+    #[test]
+    fn cci_roc_and_awesome_oscillator() {
+        let mut cci = Cci::new(3);
+        let mut roc = Roc::new(2);
+        let mut ao = AwesomeOscillator::new(1, 3);
+
+        for &x in [1f64, 2f64, 3f64, 4f64].iter() {
+            cci.next(x, x, x);
+            roc.next(x);
+            ao.next(x + 1f64, x - 1f64);
+        }
+
+        // Typical prices 2, 3, 4: mean 3, mean deviation 2 / 3.
+        assert!((cci.get().unwrap() - 100f64).abs() < 1e-9);
+        assert_eq!(roc.get(), Some(100f64));
+        assert_eq!(roc.momentum(), Some(2f64));
+        assert_eq!(ao.get(), Some(1f64));
+    }
+}
@@ -0,0 +1,467 @@
+//! Candlestick patterns. [`CandlePatterns`] reports the patterns completed by
+//! each new kline, single kline patterns on that kline and multi kline ones
+//! ending on it.
+//!
+//! Every pattern comes with a strength from 0 to 1 telling how clean the
+//! shape is. Patterns may overlap, a hammer usually is a bullish pin bar too.
+
+use std::collections::VecDeque;
+
+use crate::exchange::Kline;
+
+/// Klines kept, the longest pattern plus the trend before it.
+const HISTORY: usize = 7;
+/// Klines before a pattern that set the trend it reverses.
+const TREND: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Bullish,
+    Bearish,
+    Neutral,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PatternKind {
+    Doji,
+    DragonflyDoji,
+    GravestoneDoji,
+    LongLeggedDoji,
+    /// Small body on top of a long lower shadow after a decline.
+    Hammer,
+    /// The hammer shape after an advance.
+    HangingMan,
+    /// A long shadow on one side, in any trend.
+    PinBar,
+    Engulfing,
+    Harami,
+    MorningStar,
+    EveningStar,
+    ThreeWhiteSoldiers,
+    ThreeBlackCrows,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub direction: Direction,
+    pub strength: f64,
+}
+
+impl Pattern {
+    fn new(kind: PatternKind, direction: Direction, strength: f64) -> Self {
+        Self {
+            kind,
+            direction,
+            strength: strength.clamp(0f64, 1f64),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Candle {
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+}
+
+impl Candle {
+    fn body(&self) -> f64 {
+        (self.close - self.open).abs()
+    }
+
+    fn range(&self) -> f64 {
+        self.high - self.low
+    }
+
+    fn body_top(&self) -> f64 {
+        self.open.max(self.close)
+    }
+
+    fn body_bottom(&self) -> f64 {
+        self.open.min(self.close)
+    }
+
+    fn upper_shadow(&self) -> f64 {
+        self.high - self.body_top()
+    }
+
+    fn lower_shadow(&self) -> f64 {
+        self.body_bottom() - self.low
+    }
+
+    fn is_bullish(&self) -> bool {
+        self.close > self.open
+    }
+
+    fn is_bearish(&self) -> bool {
+        self.close < self.open
+    }
+
+    /// Body of at least half the range.
+    fn is_long(&self) -> bool {
+        self.range() > 0f64 && self.body() >= 0.5f64 * self.range()
+    }
+}
+
+pub struct CandlePatterns {
+    /// Last klines, oldest first.
+    candles: VecDeque<Candle>,
+    patterns: Vec<Pattern>,
+}
+
+impl Default for CandlePatterns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CandlePatterns {
+    pub fn new() -> Self {
+        Self {
+            candles: VecDeque::with_capacity(HISTORY),
+            patterns: Vec::new(),
+        }
+    }
+
+    pub fn next(&mut self, kline: &Kline) {
+        if self.candles.len() == HISTORY {
+            self.candles.pop_front();
+        }
+        self.candles.push_back(Candle {
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+        });
+
+        self.patterns.clear();
+        self.single();
+        self.double();
+        self.triple();
+    }
+
+    /// Patterns completed by the last kline.
+    pub fn get(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    /// Bullish strengths less bearish ones, a single input for a strategy.
+    pub fn bias(&self) -> f64 {
+        self.patterns
+            .iter()
+            .map(|p| match p.direction {
+                Direction::Bullish => p.strength,
+                Direction::Bearish => -p.strength,
+                Direction::Neutral => 0f64,
+            })
+            .sum()
+    }
+
+    /// The `i`th kline from the end, 0 being the last one.
+    fn back(&self, i: usize) -> Candle {
+        self.candles[self.candles.len() - 1 - i]
+    }
+
+    /// Direction of the closes over the [`TREND`] klines before the last
+    /// `len`, if there are enough of them.
+    fn trend_before(&self, len: usize) -> Option<Direction> {
+        if self.candles.len() < len + TREND {
+            return None;
+        }
+
+        let last = self.back(len).close;
+        let first = self.back(len + TREND - 1).close;
+
+        Some(if last < first {
+            Direction::Bearish
+        } else if last > first {
+            Direction::Bullish
+        } else {
+            Direction::Neutral
+        })
+    }
+
+    fn push(&mut self, kind: PatternKind, direction: Direction, strength: f64) {
+        self.patterns.push(Pattern::new(kind, direction, strength));
+    }
+
+    fn single(&mut self) {
+        let c = self.back(0);
+        let range = c.range();
+        if range <= 0f64 {
+            return;
+        }
+
+        let body = c.body() / range;
+        let upper = c.upper_shadow() / range;
+        let lower = c.lower_shadow() / range;
+
+        // Doji, a body of at most a tenth of the range.
+        if body <= 0.1f64 {
+            let strength = 1f64 - body / 0.1f64;
+            let (kind, direction) = if upper <= 0.1f64 {
+                (PatternKind::DragonflyDoji, Direction::Bullish)
+            } else if lower <= 0.1f64 {
+                (PatternKind::GravestoneDoji, Direction::Bearish)
+            } else if upper >= 0.3f64 && lower >= 0.3f64 {
+                (PatternKind::LongLeggedDoji, Direction::Neutral)
+            } else {
+                (PatternKind::Doji, Direction::Neutral)
+            };
+            self.push(kind, direction, strength);
+        }
+
+        // Hammer shape, a lower shadow of at least twice a small body.
+        if body > 0f64 && body <= 0.35f64 && upper <= 0.1f64 && lower >= 2f64 * body {
+            match self.trend_before(1) {
+                Some(Direction::Bearish) => {
+                    self.push(PatternKind::Hammer, Direction::Bullish, lower)
+                }
+                Some(Direction::Bullish) => {
+                    self.push(PatternKind::HangingMan, Direction::Bearish, lower)
+                }
+                _ => {}
+            }
+        }
+
+        // Pin bar, one shadow of at least two thirds of the range.
+        if lower >= 2f64 / 3f64 {
+            self.push(PatternKind::PinBar, Direction::Bullish, lower);
+        } else if upper >= 2f64 / 3f64 {
+            self.push(PatternKind::PinBar, Direction::Bearish, upper);
+        }
+    }
+
+    fn double(&mut self) {
+        if self.candles.len() < 2 {
+            return;
+        }
+
+        let (prev, c) = (self.back(1), self.back(0));
+        let opposite =
+            (prev.is_bullish() && c.is_bearish()) || (prev.is_bearish() && c.is_bullish());
+        if !opposite {
+            return;
+        }
+
+        let direction = if c.is_bullish() {
+            Direction::Bullish
+        } else {
+            Direction::Bearish
+        };
+
+        // The body swallows the previous one, larger is stronger.
+        if c.body_top() >= prev.body_top()
+            && c.body_bottom() <= prev.body_bottom()
+            && c.body() > prev.body()
+        {
+            self.push(
+                PatternKind::Engulfing,
+                direction,
+                1f64 - prev.body() / c.body(),
+            );
+        }
+
+        // The body fits in a long previous one, smaller is stronger.
+        if prev.is_long()
+            && c.body_top() <= prev.body_top()
+            && c.body_bottom() >= prev.body_bottom()
+            && c.body() < prev.body()
+        {
+            self.push(
+                PatternKind::Harami,
+                direction,
+                1f64 - c.body() / prev.body(),
+            );
+        }
+    }
+
+    fn triple(&mut self) {
+        if self.candles.len() < 3 {
+            return;
+        }
+
+        let (first, second, third) = (self.back(2), self.back(1), self.back(0));
+
+        // A long kline, a small one gapping away from it, and a long one back
+        // past the middle of the first. Closing deeper into it is stronger.
+        if first.is_long() && third.is_long() && second.body() <= 0.3f64 * first.body() {
+            let middle = (first.open + first.close) / 2f64;
+            let half = first.body() / 2f64;
+
+            if first.is_bearish()
+                && third.is_bullish()
+                && second.body_top() <= first.close
+                && third.close > middle
+            {
+                self.push(
+                    PatternKind::MorningStar,
+                    Direction::Bullish,
+                    (third.close - middle) / half,
+                );
+            } else if first.is_bullish()
+                && third.is_bearish()
+                && second.body_bottom() >= first.close
+                && third.close < middle
+            {
+                self.push(
+                    PatternKind::EveningStar,
+                    Direction::Bearish,
+                    (middle - third.close) / half,
+                );
+            }
+        }
+
+        // Three long klines closing further each time and opening inside the
+        // previous body. Longer bodies are stronger.
+        let candles = [first, second, third];
+        if !candles.iter().all(Candle::is_long) {
+            return;
+        }
+        let strength = candles.iter().map(|c| c.body() / c.range()).sum::<f64>() / 3f64;
+        let opens_inside = candles
+            .windows(2)
+            .all(|w| w[1].open >= w[0].body_bottom() && w[1].open <= w[0].body_top());
+
+        if !opens_inside {
+            return;
+        }
+
+        if candles.iter().all(Candle::is_bullish)
+            && candles.windows(2).all(|w| w[1].close > w[0].close)
+        {
+            self.push(
+                PatternKind::ThreeWhiteSoldiers,
+                Direction::Bullish,
+                strength,
+            );
+        } else if candles.iter().all(Candle::is_bearish)
+            && candles.windows(2).all(|w| w[1].close < w[0].close)
+        {
+            self.push(PatternKind::ThreeBlackCrows, Direction::Bearish, strength);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline(open: f64, high: f64, low: f64, close: f64) -> Kline {
+        Kline {
+            open,
+            high,
+            low,
+            close,
+            ..Kline::default()
+        }
+    }
+
+    fn detect(klines: &[Kline]) -> Vec<Pattern> {
+        let mut patterns = CandlePatterns::new();
+        for kline in klines {
+            patterns.next(kline);
+        }
+        patterns.get().to_vec()
+    }
+
+    fn kinds(patterns: &[Pattern]) -> Vec<PatternKind> {
+        patterns.iter().map(|p| p.kind).collect()
+    }
+
+    #[test]
+    fn single_kline_patterns() {
+        assert_eq!(
+            detect(&[kline(10f64, 10f64, 8f64, 10f64)]),
+            vec![
+                Pattern::new(PatternKind::DragonflyDoji, Direction::Bullish, 1f64),
+                Pattern::new(PatternKind::PinBar, Direction::Bullish, 1f64),
+            ]
+        );
+        assert_eq!(
+            kinds(&detect(&[kline(10f64, 11f64, 9f64, 10.1f64)])),
+            vec![PatternKind::LongLeggedDoji]
+        );
+
+        // The same shape after a decline and after an advance.
+        let hammer = kline(10f64, 10.6f64, 7f64, 10.5f64);
+        let decline = [
+            kline(14f64, 14f64, 13f64, 13f64),
+            kline(13f64, 13f64, 12f64, 12f64),
+            kline(12f64, 12f64, 11f64, 11f64),
+        ];
+        let patterns = detect(&[&decline[..], std::slice::from_ref(&hammer)].concat());
+        assert_eq!(patterns[0].kind, PatternKind::Hammer);
+        assert_eq!(patterns[0].direction, Direction::Bullish);
+        assert!((patterns[0].strength - 3f64 / 3.6f64).abs() < 1e-12);
+
+        let advance: Vec<_> = decline.iter().rev().cloned().collect();
+        let patterns = detect(&[&advance[..], &[hammer]].concat());
+        assert_eq!(patterns[0].kind, PatternKind::HangingMan);
+        assert_eq!(patterns[0].direction, Direction::Bearish);
+    }
+
+    #[test]
+    fn two_kline_patterns() {
+        let engulfing = detect(&[
+            kline(10f64, 10.2f64, 8.8f64, 9f64),
+            kline(8.5f64, 11.5f64, 8.4f64, 11f64),
+        ]);
+        assert_eq!(
+            engulfing,
+            vec![Pattern::new(
+                PatternKind::Engulfing,
+                Direction::Bullish,
+                1f64 - 1f64 / 2.5f64
+            )]
+        );
+
+        let harami = detect(&[
+            kline(10f64, 10.2f64, 7.8f64, 8f64),
+            kline(8.5f64, 9.7f64, 8.4f64, 9.5f64),
+        ]);
+        assert_eq!(kinds(&harami), vec![PatternKind::Harami]);
+        assert_eq!(harami[0].direction, Direction::Bullish);
+        assert!((harami[0].strength - 0.5f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn three_kline_patterns() {
+        let mut patterns = CandlePatterns::new();
+        for kline in [
+            kline(20f64, 20.5f64, 15.5f64, 16f64),
+            kline(15.5f64, 15.8f64, 14.8f64, 15f64),
+            kline(15.5f64, 19.2f64, 15.4f64, 19f64),
+        ]
+        .iter()
+        {
+            patterns.next(kline);
+        }
+        // Closing at 19 is half way from the middle at 18 to the top at 20.
+        assert_eq!(
+            patterns.get(),
+            &[Pattern::new(
+                PatternKind::MorningStar,
+                Direction::Bullish,
+                0.5f64
+            )]
+        );
+        assert_eq!(patterns.bias(), 0.5f64);
+
+        let crows = detect(&[
+            kline(20f64, 20f64, 18f64, 18f64),
+            kline(19f64, 19f64, 17f64, 17f64),
+            kline(18f64, 18f64, 16f64, 16f64),
+        ]);
+        assert_eq!(
+            crows,
+            vec![Pattern::new(
+                PatternKind::ThreeBlackCrows,
+                Direction::Bearish,
+                1f64
+            )]
+        );
+    }
+}
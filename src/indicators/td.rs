@@ -0,0 +1,315 @@
+//! DeMark's TD Sequential: the nine bar setup, the thirteen bar countdown
+//! that follows it and the TDST levels drawn from the setup.
+//!
+//! Buy setups and countdowns are positive, sell ones negative.
+
+const SETUP: i32 = 9;
+const COUNTDOWN: u32 = 13;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Bar {
+    high: f64,
+    low: f64,
+    close: f64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Countdown {
+    /// Bars counted so far, stays at 12 while the thirteenth is deferred.
+    pub count: u32,
+    close_8: f64,
+}
+
+/// State of the sequence after the last kline.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TdSeqValue {
+    pub setup_count: i32,
+    /// The setup completed on this kline with a perfected eighth or ninth
+    /// bar.
+    pub perfect: bool,
+    pub buy_countdown: Option<Countdown>,
+    pub sell_countdown: Option<Countdown>,
+    /// A countdown reached a qualified thirteen on this kline.
+    pub buy_countdown_complete: bool,
+    pub sell_countdown_complete: bool,
+    /// Lowest true low of the last sell setup.
+    pub tdst_support: Option<f64>,
+    /// Highest true high of the last buy setup.
+    pub tdst_resistance: Option<f64>,
+}
+
+pub struct TdSeq {
+    /// Last five klines, oldest first.
+    bars: Vec<Bar>,
+    pub setup_count: i32,
+    perfect: bool,
+    setup_high: f64,
+    setup_low: f64,
+    value: TdSeqValue,
+}
+
+impl Default for TdSeq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TdSeq {
+    pub fn new() -> Self {
+        Self {
+            bars: Vec::with_capacity(5),
+            setup_count: 0,
+            perfect: false,
+            setup_high: f64::NAN,
+            setup_low: f64::NAN,
+            value: TdSeqValue::default(),
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64, close: f64) {
+        let prev_close = self.bars.last().map_or(close, |bar| bar.close);
+
+        if self.bars.len() == 5 {
+            self.bars.remove(0);
+        }
+        self.bars.push(Bar { high, low, close });
+
+        self.value.buy_countdown_complete = false;
+        self.value.sell_countdown_complete = false;
+
+        if self.bars.len() < 5 {
+            return;
+        }
+
+        self.setup(high.max(prev_close), low.min(prev_close));
+        self.cancel_countdowns(close);
+        self.countdown();
+
+        self.value.setup_count = self.setup_count;
+        self.value.perfect = self.perfect && self.setup_count.abs() == SETUP;
+    }
+
+    /// Counts closes below (above) the close four bars earlier. Any other
+    /// close, including an equal one, interrupts the setup.
+    fn setup(&mut self, true_high: f64, true_low: f64) {
+        let close = self.bars[4].close;
+        let close_4 = self.bars[0].close;
+
+        self.setup_count = if close < close_4 {
+            if self.setup_count <= 0 || self.setup_count == SETUP {
+                1
+            } else {
+                self.setup_count + 1
+            }
+        } else if close > close_4 {
+            if self.setup_count >= 0 || self.setup_count == -SETUP {
+                -1
+            } else {
+                self.setup_count - 1
+            }
+        } else {
+            0
+        };
+
+        if self.setup_count.abs() == 1 {
+            self.setup_high = true_high;
+            self.setup_low = true_low;
+        } else {
+            self.setup_high = self.setup_high.max(true_high);
+            self.setup_low = self.setup_low.min(true_low);
+        }
+
+        if self.setup_count.abs() != SETUP {
+            return;
+        }
+
+        // Bars six to nine of the setup are the last four.
+        let bars = &self.bars[1..];
+        let countdown = Some(Countdown {
+            count: 0,
+            close_8: f64::NAN,
+        });
+
+        if self.setup_count > 0 {
+            let lows = [bars[0].low, bars[1].low];
+            self.perfect = [bars[2].low, bars[3].low]
+                .iter()
+                .any(|&low| lows.iter().all(|&l| low < l));
+
+            // A setup starts its countdown unless one is already running,
+            // and cancels the opposite one.
+            self.value.tdst_resistance = Some(self.setup_high);
+            self.value.buy_countdown = self.value.buy_countdown.or(countdown);
+            self.value.sell_countdown = None;
+        } else {
+            let highs = [bars[0].high, bars[1].high];
+            self.perfect = [bars[2].high, bars[3].high]
+                .iter()
+                .any(|&high| highs.iter().all(|&h| high > h));
+
+            self.value.tdst_support = Some(self.setup_low);
+            self.value.sell_countdown = self.value.sell_countdown.or(countdown);
+            self.value.buy_countdown = None;
+        }
+    }
+
+    /// A close beyond the TDST level of the last setup in the direction of a
+    /// countdown cancels it.
+    fn cancel_countdowns(&mut self, close: f64) {
+        if matches!(self.value.tdst_resistance, Some(level) if close > level) {
+            self.value.buy_countdown = None;
+        }
+
+        if matches!(self.value.tdst_support, Some(level) if close < level) {
+            self.value.sell_countdown = None;
+        }
+    }
+
+    /// Counts closes at or below the low (at or above the high) two bars
+    /// earlier. The thirteenth bar must also trade through the close of the
+    /// eighth, otherwise it is deferred to the next bar that does.
+    fn countdown(&mut self) {
+        let bar = self.bars[4];
+        let bar_2 = self.bars[2];
+
+        if let Some(countdown) = self.value.buy_countdown.as_mut() {
+            if bar.close <= bar_2.low && step(countdown, bar.close, bar.low <= countdown.close_8) {
+                self.value.buy_countdown = None;
+                self.value.buy_countdown_complete = true;
+            }
+        }
+
+        if let Some(countdown) = self.value.sell_countdown.as_mut() {
+            if bar.close >= bar_2.high && step(countdown, bar.close, bar.high >= countdown.close_8)
+            {
+                self.value.sell_countdown = None;
+                self.value.sell_countdown_complete = true;
+            }
+        }
+    }
+
+    pub fn buy_perfect(&self) -> bool {
+        self.perfect && self.setup_count == SETUP
+    }
+
+    pub fn sell_perfect(&self) -> bool {
+        self.perfect && self.setup_count == -SETUP
+    }
+
+    pub fn get(&self) -> TdSeqValue {
+        self.value
+    }
+}
+
+/// Counts a qualifying bar, returns whether it completes the countdown.
+fn step(countdown: &mut Countdown, close: f64, qualified: bool) -> bool {
+    if countdown.count == COUNTDOWN - 1 {
+        return qualified;
+    }
+
+    countdown.count += 1;
+    if countdown.count == 8 {
+        countdown.close_8 = close;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds klines one unit wide around the closes.
+    fn feed(td_seq: &mut TdSeq, closes: &[f64]) {
+        for &close in closes {
+            td_seq.next(close + 0.5f64, close - 0.5f64, close);
+        }
+    }
+
+    #[test]
+    fn buy_setup_countdown_and_tdst() {
+        let mut td_seq = TdSeq::new();
+
+        // Four flat bars, then a steady decline.
+        feed(&mut td_seq, &[100f64; 4]);
+        feed(
+            &mut td_seq,
+            &(1..=9).map(|i| 100f64 - i as f64).collect::<Vec<_>>(),
+        );
+
+        let value = td_seq.get();
+        assert_eq!(value.setup_count, 9);
+        assert!(value.perfect && td_seq.buy_perfect());
+        assert_eq!(value.tdst_resistance, Some(100f64));
+        // The ninth bar closes below the low two bars earlier.
+        assert_eq!(value.buy_countdown.unwrap().count, 1);
+
+        // The second setup completing at 82 keeps the running countdown.
+        feed(
+            &mut td_seq,
+            &(10..=20).map(|i| 100f64 - i as f64).collect::<Vec<_>>(),
+        );
+        let value = td_seq.get();
+        assert_eq!(value.buy_countdown.unwrap().count, 12);
+        assert_eq!(value.tdst_resistance, Some(91f64));
+        assert!(!value.buy_countdown_complete);
+
+        feed(&mut td_seq, &[79f64]);
+        assert!(td_seq.get().buy_countdown_complete);
+        assert_eq!(td_seq.get().buy_countdown, None);
+    }
+
+    #[test]
+    fn thirteen_waits_for_bar_8_qualifier() {
+        let mut countdown = Countdown::default();
+
+        for i in 1..=12 {
+            assert!(!step(&mut countdown, 100f64 - i as f64, true));
+        }
+        assert_eq!(countdown.close_8, 92f64);
+
+        assert!(!step(&mut countdown, 90f64, false));
+        assert_eq!(countdown.count, 12);
+        assert!(step(&mut countdown, 89f64, true));
+    }
+
+    #[test]
+    fn countdown_cancellation() {
+        let mut td_seq = TdSeq::new();
+
+        feed(&mut td_seq, &[100f64; 4]);
+        feed(
+            &mut td_seq,
+            &(1..=9).map(|i| 100f64 - i as f64).collect::<Vec<_>>(),
+        );
+        assert!(td_seq.get().buy_countdown.is_some());
+
+        // A close above the TDST resistance.
+        feed(&mut td_seq, &[101f64]);
+        assert_eq!(td_seq.get().buy_countdown, None);
+
+        // An equal close four bars apart interrupts the setup.
+        feed(&mut td_seq, &[95f64, 94f64, 93f64, 101f64]);
+        assert_eq!(td_seq.setup_count, 0);
+
+        // A sell setup below the TDST resistance.
+        let mut td_seq = TdSeq::new();
+        feed(&mut td_seq, &[100f64; 4]);
+        feed(
+            &mut td_seq,
+            &(1..=9).map(|i| 100f64 - i as f64).collect::<Vec<_>>(),
+        );
+        feed(
+            &mut td_seq,
+            &(0..9)
+                .map(|i| 94.5f64 + 0.5f64 * i as f64)
+                .collect::<Vec<_>>(),
+        );
+
+        let value = td_seq.get();
+        assert_eq!(value.setup_count, -9);
+        assert_eq!(value.buy_countdown, None);
+        assert_eq!(value.sell_countdown.unwrap().count, 1);
+        assert_eq!(value.tdst_support, Some(91f64));
+    }
+}
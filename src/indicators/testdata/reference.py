@@ -0,0 +1,401 @@
+"""Reference values for the indicator tests in `reference.rs`.
+
+Plain implementations of the textbook formulas, written independently of the
+Rust code: Wilder smoothing and EMAs are seeded with the SMA of the first
+`period` values like TA-Lib does. These are not TA-Lib or TradingView
+outputs, no outside library was run on the fixture. Run `python3 reference.py`
+from this directory and copy the printed tables into `reference.rs`.
+"""
+
+import csv
+import math
+
+CHECKPOINTS = [40, 75, 110, 149]
+
+
+def load():
+    with open("klines.csv") as f:
+        rows = list(csv.DictReader(f))
+    return [{k: float(v) for k, v in row.items()} for row in rows]
+
+
+def sma(xs, n):
+    return [sum(xs[i + 1 - n : i + 1]) / n if i + 1 >= n else None for i in range(len(xs))]
+
+
+def std(xs, n):
+    out = []
+    for i in range(len(xs)):
+        if i + 1 < n:
+            out.append(None)
+            continue
+        w = xs[i + 1 - n : i + 1]
+        m = sum(w) / n
+        out.append(math.sqrt(sum((x - m) ** 2 for x in w) / n))
+    return out
+
+
+def smoothed(xs, n, alpha):
+    """SMA seeded exponential smoothing over the defined values of `xs`."""
+    out, value, seen = [], None, []
+    for x in xs:
+        if x is None:
+            out.append(None)
+            continue
+        if value is None:
+            seen.append(x)
+            if len(seen) == n:
+                value = sum(seen) / n
+        else:
+            value += alpha * (x - value)
+        out.append(value)
+    return out
+
+
+def ema(xs, n):
+    return smoothed(xs, n, 2 / (n + 1))
+
+
+def rma(xs, n):
+    return smoothed(xs, n, 1 / n)
+
+
+def dema(xs, n):
+    e1 = ema(xs, n)
+    e2 = ema(e1, n)
+    return [2 * a - b if a is not None and b is not None else None for a, b in zip(e1, e2)]
+
+
+def tema(xs, n):
+    e1 = ema(xs, n)
+    e2 = ema(e1, n)
+    e3 = ema(e2, n)
+    return [3 * a - 3 * b + c if c is not None else None for a, b, c in zip(e1, e2, e3)]
+
+
+def wma(xs, n):
+    out = []
+    for i in range(len(xs)):
+        w = xs[i + 1 - n : i + 1] if i + 1 >= n else []
+        if len(w) < n or any(x is None for x in w):
+            out.append(None)
+        else:
+            out.append(sum((j + 1) * x for j, x in enumerate(w)) / (n * (n + 1) / 2))
+    return out
+
+
+def hma(xs, n):
+    half, full = wma(xs, n // 2), wma(xs, n)
+    diff = [2 * a - b for a, b in zip(half, full) if b is not None]
+    hull = wma(diff, round(math.sqrt(n)))
+    return [None] * (len(xs) - len(hull)) + hull
+
+
+def kama(xs, n, fast, slow):
+    """Seeded with the close before the first full efficiency window."""
+    fast_sc, slow_sc = 2 / (fast + 1), 2 / (slow + 1)
+    out, value = [None] * n, xs[n - 1]
+    for i in range(n, len(xs)):
+        change = abs(xs[i] - xs[i - n])
+        volatility = sum(abs(xs[j] - xs[j - 1]) for j in range(i + 1 - n, i + 1))
+        er = change / volatility if volatility > 0 else 0.0
+        sc = (er * (fast_sc - slow_sc) + slow_sc) ** 2
+        value += sc * (xs[i] - value)
+        out.append(value)
+    return out
+
+
+def vwma(ks, n):
+    return [
+        sum(k["close"] * k["volume"] for k in ks[i + 1 - n : i + 1]) / sum(k["volume"] for k in ks[i + 1 - n : i + 1])
+        if i + 1 >= n
+        else None
+        for i in range(len(ks))
+    ]
+
+
+def macd(xs, fast, slow, signal):
+    line = [a - b if a is not None and b is not None else None for a, b in zip(ema(xs, fast), ema(xs, slow))]
+    sig = ema(line, signal)
+    hist = [a - b if b is not None else None for a, b in zip(line, sig)]
+    return line, sig, hist
+
+
+def rolling(xs, n, f):
+    """`f` over the last `n` values, or fewer at the start."""
+    return [f(xs[max(0, i + 1 - n) : i + 1]) for i in range(len(xs))]
+
+
+def previous(ks, f):
+    return [None] + [f(ks[i], ks[i - 1]) for i in range(1, len(ks))]
+
+
+def atr(ks, n):
+    tr = previous(ks, lambda k, p: max(k["high"], p["close"]) - min(k["low"], p["close"]))
+    return rma(tr, n)
+
+
+def dmi(ks, n):
+    def pdm(k, p):
+        up, down = k["high"] - p["high"], p["low"] - k["low"]
+        return up if up > down and up > 0 else 0.0
+
+    def mdm(k, p):
+        up, down = k["high"] - p["high"], p["low"] - k["low"]
+        return down if down > up and down > 0 else 0.0
+
+    a = atr(ks, n)
+    pdi, mdi, dx = [], [], []
+    for p, m, t in zip(rma(previous(ks, pdm), n), rma(previous(ks, mdm), n), a):
+        if p is None or t is None:
+            pdi.append(None)
+            mdi.append(None)
+            dx.append(None)
+        else:
+            pdi.append(100 * p / t)
+            mdi.append(100 * m / t)
+            dx.append(100 * abs(pdi[-1] - mdi[-1]) / (pdi[-1] + mdi[-1]))
+    return rma(dx, n), pdi, mdi
+
+
+def rsi(xs, n):
+    gain = [None] + [max(xs[i] - xs[i - 1], 0.0) for i in range(1, len(xs))]
+    loss = [None] + [max(xs[i - 1] - xs[i], 0.0) for i in range(1, len(xs))]
+    return [
+        100 - 100 / (1 + g / l) if g is not None and l is not None else None
+        for g, l in zip(rma(gain, n), rma(loss, n))
+    ]
+
+
+def stoch_rsi(xs, n):
+    r = rsi(xs, n)
+    stoch = []
+    for i in range(len(r)):
+        w = [x for x in r[max(0, i + 1 - n) : i + 1] if x is not None]
+        if len(w) < n:
+            stoch.append(None)
+        else:
+            high, low = max(w), min(w)
+            stoch.append(100 * (r[i] - low) / (high - low) if high > low else 0.0)
+    return [
+        sum(stoch[i + 1 - n : i + 1]) / n if i + 1 >= n and stoch[i + 1 - n] is not None else None
+        for i in range(len(stoch))
+    ]
+
+
+def stochastic(ks, n, k_smoothing, d_period):
+    raw = []
+    for i in range(len(ks)):
+        if i + 1 < n:
+            raw.append(None)
+            continue
+        w = ks[i + 1 - n : i + 1]
+        high, low = max(k["high"] for k in w), min(k["low"] for k in w)
+        raw.append(100 * (ks[i]["close"] - low) / (high - low) if high > low else 0.0)
+    k = defined_sma(raw, k_smoothing)
+    return k, defined_sma(k, d_period)
+
+
+def defined_sma(xs, n):
+    """SMA over the defined values of `xs`."""
+    out, seen = [], []
+    for x in xs:
+        if x is not None:
+            seen.append(x)
+        out.append(sum(seen[-n:]) / n if x is not None and len(seen) >= n else None)
+    return out
+
+
+def williams_r(ks, n):
+    out = []
+    for i in range(len(ks)):
+        if i + 1 < n:
+            out.append(None)
+            continue
+        w = ks[i + 1 - n : i + 1]
+        high, low = max(k["high"] for k in w), min(k["low"] for k in w)
+        out.append(-100 * (high - ks[i]["close"]) / (high - low) if high > low else -100.0)
+    return out
+
+
+def cci(ks, n):
+    tp = [(k["high"] + k["low"] + k["close"]) / 3 for k in ks]
+    out = []
+    for i in range(len(tp)):
+        if i + 1 < n:
+            out.append(None)
+            continue
+        w = tp[i + 1 - n : i + 1]
+        m = sum(w) / n
+        md = sum(abs(x - m) for x in w) / n
+        out.append((tp[i] - m) / (0.015 * md) if md > 0 else 0.0)
+    return out
+
+
+def roc(xs, n):
+    return [None] * n + [100 * (xs[i] - xs[i - n]) / xs[i - n] for i in range(n, len(xs))]
+
+
+def momentum(xs, n):
+    return [None] * n + [xs[i] - xs[i - n] for i in range(n, len(xs))]
+
+
+def awesome(ks, fast, slow):
+    median = [(k["high"] + k["low"]) / 2 for k in ks]
+    return [a - b if b is not None else None for a, b in zip(sma(median, fast), sma(median, slow))]
+
+
+def donchian(ks, n):
+    return [
+        (max(k["high"] for k in ks[i + 1 - n : i + 1]) + min(k["low"] for k in ks[i + 1 - n : i + 1])) / 2
+        if i + 1 >= n
+        else None
+        for i in range(len(ks))
+    ]
+
+
+def keltner_upper(ks, n, atr_n, m):
+    middle = ema([k["close"] for k in ks], n)
+    return [a + m * t if a is not None and t is not None else None for a, t in zip(middle, atr(ks, atr_n))]
+
+
+def supertrend(ks, n, m):
+    out, upper, lower, up = [], None, None, True
+    for i, t in enumerate(atr(ks, n)):
+        if t is None:
+            out.append(None)
+            continue
+        median = (ks[i]["high"] + ks[i]["low"]) / 2
+        close, prev = ks[i]["close"], ks[i - 1]["close"]
+        basic_upper, basic_lower = median + m * t, median - m * t
+        upper = basic_upper if upper is None or basic_upper < upper or prev > upper else upper
+        lower = basic_lower if lower is None or basic_lower > lower or prev < lower else lower
+        if up and close < lower:
+            up = False
+        elif not up and close > upper:
+            up = True
+        out.append(lower if up else upper)
+    return out
+
+
+def parabolic_sar(ks, step, max_step):
+    out = [None]
+    long = ks[1]["high"] >= ks[0]["high"]
+    sar = ks[0]["low"] if long else ks[0]["high"]
+    ep = ks[1]["high"] if long else ks[1]["low"]
+    af = step
+    out.append(sar)
+    for i in range(2, len(ks)):
+        sar += af * (ep - sar)
+        if long:
+            sar = min(sar, ks[i - 1]["low"], ks[i - 2]["low"])
+            if ks[i]["low"] < sar:
+                long, sar, ep, af = False, ep, ks[i]["low"], step
+            elif ks[i]["high"] > ep:
+                ep, af = ks[i]["high"], min(af + step, max_step)
+        else:
+            sar = max(sar, ks[i - 1]["high"], ks[i - 2]["high"])
+            if ks[i]["high"] > sar:
+                long, sar, ep, af = True, ep, ks[i]["high"], step
+            elif ks[i]["low"] < ep:
+                ep, af = ks[i]["low"], min(af + step, max_step)
+        out.append(sar)
+    return out
+
+
+def senkou(ks, tenkan, kijun, senkou_n, displacement):
+    t, k, b = donchian(ks, tenkan), donchian(ks, kijun), donchian(ks, senkou_n)
+    a = [(x + y) / 2 if x is not None and y is not None else None for x, y in zip(t, k)]
+    return [None] * displacement + a[:-displacement], [None] * displacement + b[:-displacement]
+
+
+def td_setup(xs):
+    """Setup count, positive while closing below the close four bars
+    earlier, negative while closing above it, restarting after nine."""
+    out, count = [], 0
+    for i in range(len(xs)):
+        if i >= 4:
+            if xs[i] < xs[i - 4]:
+                count = 1 if count < 0 or count == 9 else count + 1
+            elif xs[i] > xs[i - 4]:
+                count = -1 if count > 0 or count == -9 else count - 1
+            else:
+                raise ValueError("fixture has a tie four bars apart")
+        out.append(count)
+    return out
+
+
+def td_perfect(ks, setup):
+    """Bars completing a perfect nine: the low (high) of bar eight or nine
+    below (above) the lows (highs) of bars six and seven."""
+    out = []
+    for i, count in enumerate(setup):
+        if abs(count) != 9:
+            continue
+        key, better = ("low", lambda a, b: a < b) if count > 0 else ("high", lambda a, b: a > b)
+        six, seven, eight, nine = (ks[i - j][key] for j in (3, 2, 1, 0))
+        if any(better(x, six) and better(x, seven) for x in (eight, nine)):
+            out.append(i)
+    return out
+
+
+def show(name, values):
+    first = next(i for i, v in enumerate(values) if v is not None)
+    checks = ", ".join(f"{values[i]!r}" for i in CHECKPOINTS)
+    print(f"{name}: first {first}, [{checks}]")
+
+
+def main():
+    ks = load()
+    closes = [k["close"] for k in ks]
+
+    show("sma(20)", sma(closes, 20))
+    show("std(20)", std(closes, 20))
+    show("ema(10)", ema(closes, 10))
+    show("dema(9)", dema(closes, 9))
+    show("tema(9)", tema(closes, 9))
+    show("wma(20)", wma(closes, 20))
+    show("hma(16)", hma(closes, 16))
+    show("kama(10, 2, 30)", kama(closes, 10, 2, 30))
+    show("smma(14)", rma(closes, 14))
+    show("vwma(20)", vwma(ks, 20))
+    line, sig, hist = macd(closes, 12, 26, 9)
+    show("macd", line)
+    show("macd signal", sig)
+    show("macd histogram", hist)
+    show("atr(14)", atr(ks, 14))
+    adx, pdi, mdi = dmi(ks, 14)
+    show("adx(14)", adx)
+    show("+di(14)", pdi)
+    show("-di(14)", mdi)
+    show("rsi(14)", rsi(closes, 14))
+    show("stoch_rsi(14)", stoch_rsi(closes, 14))
+    k, d = stochastic(ks, 14, 3, 3)
+    show("stochastic %k(14, 3)", k)
+    show("stochastic %d(3)", d)
+    show("williams %r(14)", williams_r(ks, 14))
+    show("cci(20)", cci(ks, 20))
+    show("roc(12)", roc(closes, 12))
+    show("momentum(10)", momentum(closes, 10))
+    show("awesome(5, 34)", awesome(ks, 5, 34))
+    show("max(14)", rolling(closes, 14, max))
+    show("min(14)", rolling(closes, 14, min))
+    basis = sma(closes, 20)
+    dev = std(closes, 20)
+    show("bb upper", [b + 2 * d if b is not None else None for b, d in zip(basis, dev)])
+    show("bb lower", [b - 2 * d if b is not None else None for b, d in zip(basis, dev)])
+    show("donchian middle(20)", donchian(ks, 20))
+    show("keltner upper(20, 10, 2)", keltner_upper(ks, 20, 10, 2))
+    show("supertrend(10, 3)", supertrend(ks, 10, 3))
+    show("parabolic sar(0.02, 0.2)", parabolic_sar(ks, 0.02, 0.2))
+    a, b = senkou(ks, 9, 26, 52, 26)
+    show("senkou a", [x if y is not None else None for x, y in zip(a, b)])
+    show("senkou b", b)
+    setup = td_setup(closes)
+    print("td setup:", setup)
+    print("td nines:", [i for i, c in enumerate(setup) if abs(c) == 9])
+    print("td perfect:", td_perfect(ks, setup))
+
+
+if __name__ == "__main__":
+    main()
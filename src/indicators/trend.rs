@@ -0,0 +1,362 @@
+use std::collections::VecDeque;
+
+use super::{Atr, Ema, Maximum, Minimum};
+
+/// Highest high and lowest low of the last `period` klines.
+pub struct Donchian {
+    period: usize,
+    count: usize,
+    maximum: Maximum,
+    minimum: Minimum,
+}
+
+impl Donchian {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            count: 0,
+            maximum: Maximum::new(period),
+            minimum: Minimum::new(period),
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64) {
+        self.maximum.next(high);
+        self.minimum.next(low);
+        self.count = (self.count + 1).min(self.period);
+    }
+
+    /// `(upper, middle, lower)` once the window is full.
+    pub fn get(&self) -> Option<(f64, f64, f64)> {
+        if self.count < self.period {
+            return None;
+        }
+
+        match (self.maximum.get(), self.minimum.get()) {
+            (Some(upper), Some(lower)) => Some((upper, (upper + lower) / 2f64, lower)),
+            _ => None,
+        }
+    }
+}
+
+/// EMA of the close with bands `m` average true ranges away.
+pub struct Keltner {
+    ema: Ema,
+    atr: Atr,
+    m: f64,
+    close_prev: Option<f64>,
+    value: Option<(f64, f64, f64)>,
+}
+
+impl Keltner {
+    pub fn new(period: usize, atr_period: usize, m: f64) -> Self {
+        Self {
+            ema: Ema::new(period),
+            atr: Atr::new(atr_period),
+            m,
+            close_prev: None,
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64, close: f64) {
+        self.ema.next(close);
+        if let Some(close_prev) = self.close_prev.replace(close) {
+            self.atr.next(high, close_prev, low);
+        }
+
+        if let (Some(middle), Some(atr)) = (self.ema.get(), self.atr.get()) {
+            self.value = Some((middle, middle + self.m * atr, middle - self.m * atr));
+        }
+    }
+
+    /// `(middle, upper, lower)` bands.
+    pub fn get(&self) -> Option<(f64, f64, f64)> {
+        self.value
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trend {
+    Up,
+    Down,
+}
+
+/// ATR bands around the median price that only ever tighten while the trend
+/// lasts. The trend flips when the close crosses the band it is riding.
+pub struct SuperTrend {
+    atr: Atr,
+    m: f64,
+    close_prev: Option<f64>,
+    upper: f64,
+    lower: f64,
+    trend: Trend,
+    value: Option<(f64, Trend)>,
+}
+
+impl SuperTrend {
+    pub fn new(period: usize, m: f64) -> Self {
+        Self {
+            atr: Atr::new(period),
+            m,
+            close_prev: None,
+            upper: f64::NAN,
+            lower: f64::NAN,
+            trend: Trend::Up,
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64, close: f64) {
+        let close_prev = match self.close_prev.replace(close) {
+            Some(close_prev) => close_prev,
+            None => return,
+        };
+
+        self.atr.next(high, close_prev, low);
+        let atr = match self.atr.get() {
+            Some(atr) => atr,
+            None => return,
+        };
+
+        let median = (high + low) / 2f64;
+        let upper = median + self.m * atr;
+        let lower = median - self.m * atr;
+
+        // The bands start out as NaN before the first ATR.
+        self.upper = if upper < self.upper || close_prev > self.upper || self.upper.is_nan() {
+            upper
+        } else {
+            self.upper
+        };
+        self.lower = if lower > self.lower || close_prev < self.lower || self.lower.is_nan() {
+            lower
+        } else {
+            self.lower
+        };
+
+        self.trend = match self.trend {
+            Trend::Up if close < self.lower => Trend::Down,
+            Trend::Down if close > self.upper => Trend::Up,
+            trend => trend,
+        };
+
+        let line = match self.trend {
+            Trend::Up => self.lower,
+            Trend::Down => self.upper,
+        };
+        self.value = Some((line, self.trend));
+    }
+
+    /// The band in use as a trailing stop, and the trend.
+    pub fn get(&self) -> Option<(f64, Trend)> {
+        self.value
+    }
+}
+
+/// Wilder's Parabolic SAR, accelerating by `step` up to `max_step` every time
+/// the trend makes a new extreme.
+pub struct ParabolicSar {
+    step: f64,
+    max_step: f64,
+    af: f64,
+    extreme: f64,
+    sar: f64,
+    long: bool,
+    prev: Option<(f64, f64)>,
+    prev2: Option<(f64, f64)>,
+    value: Option<f64>,
+}
+
+impl ParabolicSar {
+    pub fn new(step: f64, max_step: f64) -> Self {
+        Self {
+            step,
+            max_step,
+            af: step,
+            extreme: f64::NAN,
+            sar: f64::NAN,
+            long: true,
+            prev: None,
+            prev2: None,
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64) {
+        let (prev_high, prev_low) = match self.prev {
+            Some(prev) => prev,
+            None => {
+                self.prev = Some((high, low));
+                return;
+            }
+        };
+
+        if self.value.is_none() {
+            // Start in the direction of the second kline.
+            self.long = high >= prev_high;
+            self.sar = if self.long { prev_low } else { prev_high };
+            self.extreme = if self.long { high } else { low };
+        } else {
+            let (prev2_high, prev2_low) = self.prev2.unwrap_or((prev_high, prev_low));
+            self.sar += self.af * (self.extreme - self.sar);
+
+            if self.long {
+                self.sar = self.sar.min(prev_low).min(prev2_low);
+
+                if low < self.sar {
+                    self.reverse(low);
+                } else if high > self.extreme {
+                    self.extreme = high;
+                    self.af = (self.af + self.step).min(self.max_step);
+                }
+            } else {
+                self.sar = self.sar.max(prev_high).max(prev2_high);
+
+                if high > self.sar {
+                    self.reverse(high);
+                } else if low < self.extreme {
+                    self.extreme = low;
+                    self.af = (self.af + self.step).min(self.max_step);
+                }
+            }
+        }
+
+        self.prev2 = self.prev;
+        self.prev = Some((high, low));
+        self.value = Some(self.sar);
+    }
+
+    fn reverse(&mut self, extreme: f64) {
+        self.long = !self.long;
+        self.sar = self.extreme;
+        self.extreme = extreme;
+        self.af = self.step;
+    }
+
+    pub fn is_long(&self) -> bool {
+        self.long
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IchimokuLines {
+    pub tenkan: f64,
+    pub kijun: f64,
+    /// Leading spans computed `displacement` klines ago, the cloud under the
+    /// current kline.
+    pub senkou_a: f64,
+    pub senkou_b: f64,
+}
+
+/// Ichimoku Kinko Hyo. The lagging span is the close itself shifted back and
+/// is left to the caller.
+pub struct Ichimoku {
+    tenkan: Donchian,
+    kijun: Donchian,
+    senkou: Donchian,
+    displacement: usize,
+    leading: VecDeque<(f64, f64)>,
+    value: Option<IchimokuLines>,
+}
+
+impl Default for Ichimoku {
+    fn default() -> Self {
+        Self::new(9, 26, 52, 26)
+    }
+}
+
+impl Ichimoku {
+    pub fn new(tenkan: usize, kijun: usize, senkou: usize, displacement: usize) -> Self {
+        Self {
+            tenkan: Donchian::new(tenkan),
+            kijun: Donchian::new(kijun),
+            senkou: Donchian::new(senkou),
+            displacement,
+            leading: VecDeque::with_capacity(displacement + 1),
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, high: f64, low: f64) {
+        self.tenkan.next(high, low);
+        self.kijun.next(high, low);
+        self.senkou.next(high, low);
+
+        let (tenkan, kijun, senkou) = match (self.tenkan.get(), self.kijun.get(), self.senkou.get())
+        {
+            (Some(tenkan), Some(kijun), Some(senkou)) => (tenkan.1, kijun.1, senkou.1),
+            _ => return,
+        };
+
+        self.leading.push_back(((tenkan + kijun) / 2f64, senkou));
+        if self.leading.len() > self.displacement {
+            let (senkou_a, senkou_b) = self.leading.pop_front().unwrap();
+            self.value = Some(IchimokuLines {
+                tenkan,
+                kijun,
+                senkou_a,
+                senkou_b,
+            });
+        }
+    }
+
+    pub fn get(&self) -> Option<IchimokuLines> {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supertrend_flips_on_band_cross() {
+        let mut supertrend = SuperTrend::new(3, 1f64);
+
+        for &close in [100f64, 101f64, 102f64, 103f64, 104f64, 105f64].iter() {
+            supertrend.next(close + 1f64, close - 1f64, close);
+        }
+        let (line, trend) = supertrend.get().unwrap();
+        assert_eq!(trend, Trend::Up);
+        assert!(line < 105f64);
+
+        supertrend.next(99f64, 94f64, 95f64);
+        assert_eq!(supertrend.get().unwrap().1, Trend::Down);
+    }
+
+    #[test]
+    fn parabolic_sar_reverses() {
+        let mut sar = ParabolicSar::new(0.02f64, 0.2f64);
+
+        for i in 0..10 {
+            let high = 101f64 + i as f64;
+            sar.next(high, high - 2f64);
+            assert!(sar.is_long());
+        }
+        assert!(sar.get().unwrap() < 108f64);
+
+        sar.next(105f64, 90f64);
+        assert!(!sar.is_long());
+        assert_eq!(sar.get(), Some(110f64));
+    }
+
+    #[test]
+    fn ichimoku_warm_up() {
+        let mut ichimoku = Ichimoku::new(2, 3, 4, 2);
+
+        for i in 0..5 {
+            ichimoku.next(10f64 + i as f64, i as f64);
+            assert_eq!(ichimoku.get(), None);
+        }
+
+        ichimoku.next(15f64, 5f64);
+        let lines = ichimoku.get().unwrap();
+        assert_eq!(lines.tenkan, (15f64 + 4f64) / 2f64);
+        assert_eq!(lines.senkou_b, (13f64 + 0f64) / 2f64);
+    }
+}
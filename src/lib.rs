@@ -1,6 +1,21 @@
+pub mod cli;
+pub mod dataset;
+pub mod download;
 pub mod exchange;
 pub mod finder;
 pub mod indicators;
+pub mod limits;
+pub mod montecarlo;
+pub mod optimizer;
 pub mod parser;
+pub mod portfolio;
+pub mod random;
+pub mod report;
+pub mod resample;
+pub mod risk;
+pub mod sizing;
+pub mod store;
 pub mod telegram;
+pub mod timeframe;
 pub mod trading;
+pub mod walkforward;
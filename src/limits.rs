@@ -0,0 +1,278 @@
+//! Portfolio wide limits of the live trader and the kill switch they trip.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+const HOUR: i64 = 60 * 60 * 1000;
+const DAY: i64 = 24 * HOUR;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// Fraction of the equity at the start of the UTC day.
+    pub max_daily_loss: Option<f64>,
+    /// Fraction of the highest equity.
+    pub max_drawdown: Option<f64>,
+    pub max_positions: Option<usize>,
+    /// Fraction of the equity in one base asset.
+    pub max_exposure: Option<f64>,
+    /// Orders in the last hour.
+    pub max_orders: Option<usize>,
+    /// Sell every position when the kill switch trips.
+    pub liquidate: bool,
+}
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        *self
+            == Self {
+                liquidate: self.liquidate,
+                ..Self::default()
+            }
+    }
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut limits = Vec::new();
+
+        if let Some(x) = self.max_daily_loss {
+            limits.push(format!("Daily Loss: {}%", x * 100f64));
+        }
+        if let Some(x) = self.max_drawdown {
+            limits.push(format!("Drawdown: {}%", x * 100f64));
+        }
+        if let Some(n) = self.max_positions {
+            limits.push(format!("Positions: {}", n));
+        }
+        if let Some(x) = self.max_exposure {
+            limits.push(format!("Exposure: {}%", x * 100f64));
+        }
+        if let Some(n) = self.max_orders {
+            limits.push(format!("Orders: {}/h", n));
+        }
+
+        write!(f, "{}", limits.join("  "))?;
+        if self.liquidate {
+            write!(f, "  (liquidate on kill)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Why the kill switch tripped, with the loss as a fraction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Breach {
+    DailyLoss(f64),
+    Drawdown(f64),
+}
+
+impl fmt::Display for Breach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breach::DailyLoss(x) => write!(f, "Daily loss of {:.2}%", x * 100f64),
+            Breach::Drawdown(x) => write!(f, "Drawdown of {:.2}%", x * 100f64),
+        }
+    }
+}
+
+/// Why an entry was refused.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rejection {
+    Killed,
+    Positions,
+    Exposure,
+    OrderRate,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Rejection::Killed => "kill switch tripped",
+            Rejection::Positions => "max open positions",
+            Rejection::Exposure => "max exposure",
+            Rejection::OrderRate => "max orders per hour",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Enforces `Limits` on the orders of the trader. Only entries are refused,
+/// exits always go through as they lower the risk.
+pub struct Guard {
+    limits: Limits,
+    day: i64,
+    day_equity: f64,
+    peak: f64,
+    /// Times of the orders of the last hour.
+    orders: VecDeque<i64>,
+    killed: Option<Breach>,
+}
+
+impl Guard {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            day: i64::MIN,
+            day_equity: 0f64,
+            peak: 0f64,
+            orders: VecDeque::new(),
+            killed: None,
+        }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn killed(&self) -> Option<Breach> {
+        self.killed
+    }
+
+    /// Adds the equity at `time`, returns the breach the first time a loss
+    /// limit is hit. The day starts at its first positive equity, losses are
+    /// not checked before it.
+    pub fn update(&mut self, time: i64, equity: f64) -> Option<Breach> {
+        if self.killed.is_some() {
+            return None;
+        }
+
+        let day = time.div_euclid(DAY);
+        if day != self.day || self.day_equity <= 0f64 {
+            self.day = day;
+            self.day_equity = equity;
+        }
+        self.peak = self.peak.max(equity);
+
+        let loss = |from: f64| {
+            if from > 0f64 {
+                1f64 - equity / from
+            } else {
+                0f64
+            }
+        };
+        let daily_loss = loss(self.day_equity);
+        let drawdown = loss(self.peak);
+
+        self.killed = match self.limits {
+            Limits {
+                max_daily_loss: Some(max),
+                ..
+            } if daily_loss >= max => Some(Breach::DailyLoss(daily_loss)),
+            Limits {
+                max_drawdown: Some(max),
+                ..
+            } if drawdown >= max => Some(Breach::Drawdown(drawdown)),
+            _ => None,
+        };
+        self.killed
+    }
+
+    /// Checks an entry at `time` with `positions` already open, leaving
+    /// `exposure` of the equity in the bought asset.
+    pub fn check_entry(
+        &mut self,
+        time: i64,
+        positions: usize,
+        exposure: f64,
+    ) -> Result<(), Rejection> {
+        while matches!(self.orders.front(), Some(&t) if t <= time - HOUR) {
+            self.orders.pop_front();
+        }
+
+        match self.limits {
+            _ if self.killed.is_some() => Err(Rejection::Killed),
+            Limits {
+                max_positions: Some(max),
+                ..
+            } if positions >= max => Err(Rejection::Positions),
+            Limits {
+                max_exposure: Some(max),
+                ..
+            } if exposure > max => Err(Rejection::Exposure),
+            Limits {
+                max_orders: Some(max),
+                ..
+            } if self.orders.len() >= max => Err(Rejection::OrderRate),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts an order sent at `time`.
+    pub fn record_order(&mut self, time: i64) {
+        self.orders.push_back(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kill_switch() {
+        let mut guard = Guard::new(Limits {
+            max_daily_loss: Some(0.05),
+            max_drawdown: Some(0.2),
+            ..Limits::default()
+        });
+
+        assert_eq!(guard.update(0, 100f64), None);
+        assert_eq!(guard.update(HOUR, 110f64), None);
+        // 4% down on the day, then the next day starts at 96.
+        assert_eq!(guard.update(DAY - 1, 96f64), None);
+        assert_eq!(guard.update(DAY, 96f64), None);
+        assert_eq!(guard.update(DAY + HOUR, 92f64), None);
+
+        match guard.update(DAY + 2 * HOUR, 91f64) {
+            Some(Breach::DailyLoss(x)) => assert!((x - 5f64 / 96f64).abs() < 1e-9),
+            breach => panic!("Expected a daily loss, got {:?}", breach),
+        }
+        assert!(guard.killed().is_some());
+        assert_eq!(guard.update(DAY + 3 * HOUR, 50f64), None);
+        assert_eq!(guard.check_entry(DAY, 0, 0f64), Err(Rejection::Killed));
+
+        let mut guard = Guard::new(Limits {
+            max_drawdown: Some(0.1),
+            ..Limits::default()
+        });
+        guard.update(0, 100f64);
+        assert!(matches!(
+            guard.update(DAY, 89f64),
+            Some(Breach::Drawdown(_))
+        ));
+
+        // Nothing priced yet, the day starts at the first positive equity.
+        let mut guard = Guard::new(Limits {
+            max_daily_loss: Some(0.05),
+            ..Limits::default()
+        });
+        assert_eq!(guard.update(0, 0f64), None);
+        assert_eq!(guard.update(HOUR, 100f64), None);
+        assert!(matches!(
+            guard.update(2 * HOUR, 90f64),
+            Some(Breach::DailyLoss(_))
+        ));
+    }
+
+    #[test]
+    fn entry_limits() {
+        let mut guard = Guard::new(Limits {
+            max_positions: Some(2),
+            max_exposure: Some(0.5),
+            max_orders: Some(2),
+            ..Limits::default()
+        });
+
+        assert_eq!(guard.check_entry(0, 1, 0.5), Ok(()));
+        assert_eq!(guard.check_entry(0, 2, 0.1), Err(Rejection::Positions));
+        assert_eq!(guard.check_entry(0, 0, 0.6), Err(Rejection::Exposure));
+
+        guard.record_order(0);
+        guard.record_order(HOUR / 2);
+        assert_eq!(
+            guard.check_entry(HOUR - 1, 0, 0.1),
+            Err(Rejection::OrderRate)
+        );
+        // The first order is an hour old.
+        assert_eq!(guard.check_entry(HOUR, 0, 0.1), Ok(()));
+    }
+}
@@ -1,24 +1,316 @@
-use chrono::{TimeZone, Utc};
+use chrono::Duration;
+
+use trading_rs::cli::{self, Command, Options};
+use trading_rs::download::Downloader;
 use trading_rs::exchange::binance::Account;
-use trading_rs::exchange::Interval;
-use trading_rs::trading::Trader;
+use trading_rs::exchange::Kline;
+use trading_rs::montecarlo::MonteCarlo;
+use trading_rs::optimizer::Optimizer;
+use trading_rs::parser::TomlParser;
+use trading_rs::random::Rng;
+use trading_rs::trading::{Backtester, Parameters, Symbol, Trader};
+use trading_rs::walkforward::WalkForward;
 
 fn main() {
-    let _start_time = Utc.ymd(2021, 1, 15).and_hms(0, 0, 0);
-    let _end_time = Utc.ymd(2021, 5, 6).and_hms(0, 0, 0);
+    let command = cli::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprint!("[ERROR] {}\n\n{}", e, cli::USAGE);
+        std::process::exit(2);
+    });
+
+    let result = match command {
+        Command::Live(options) => live(options, false),
+        Command::Paper(options) => live(options, true),
+        Command::Backtest(options) => backtest(options),
+        Command::Optimize(options) => optimize(options),
+        Command::WalkForward(options) => walk_forward(options),
+        Command::Download(options) => download(options),
+        Command::Balance => balance(),
+        Command::ValidateConfig => validate_config(),
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("[ERROR] {}", e);
+        std::process::exit(1);
+    }
+}
 
+fn live(options: Options, paper: bool) -> Result<(), String> {
     let binance = Account::new();
+    let mut trader = Trader::new(binance, options.interval());
+    if !options.symbols.is_empty() {
+        trader = trader.with_symbols(&options.symbol_strings())?;
+    }
+    let trader = trader
+        .with_parameters(options.parameters)
+        .with_trend_filters(&options.trend_filters)
+        .with_risk(options.risk)
+        .with_sizing(options.sizing)
+        .with_limits(options.limits)
+        .with_reference(&options.reference);
+    let mut trader = if paper { trader.paper() } else { trader };
 
-    let mut trader = Trader::new(binance, Interval::Hour(1));
     trader.run();
+    Ok(())
+}
+
+fn backtest(options: Options) -> Result<(), String> {
+    if options.symbols.is_empty() {
+        return Err(String::from("`--symbols` is required"));
+    }
+
+    let binance = Account::new();
+    let start_time = options.start_time()?;
+
+    for (base, quote) in options.symbols.iter() {
+        let symbol = Symbol::new(base, quote)
+            .with_parameters(options.parameters)
+            .with_trend_filters(&options.trend_filters)
+            .with_risk(options.risk)
+            .with_sizing(options.sizing);
+        let mut backtester =
+            Backtester::new(start_time, options.end_time(), symbol, options.interval())
+                .dir(&options.dir);
+        if let Some(source) = options.source_interval {
+            backtester = backtester.source(source);
+        }
+        let report = backtester.run(&binance);
+
+        if let Some(runs) = options.monte_carlo {
+            let mut rng = options.seed.map_or_else(Rng::from_time, Rng::new);
+            let monte_carlo = MonteCarlo::new(runs, options.resample).ruin(options.ruin);
+            println!("\n{}", monte_carlo.run(&report, &mut rng));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parameter sets to test, the whole grid or a random sample of it.
+fn candidates(options: &Options) -> Vec<Parameters> {
+    match options.samples {
+        Some(n) => {
+            let mut rng = options.seed.map_or_else(Rng::from_time, Rng::new);
+            options.grid.sample(&mut rng, n)
+        }
+        None => options.grid.parameters(),
+    }
+}
+
+/// Builds the optimizer of a symbol and loads its klines.
+fn optimizer(
+    options: &Options,
+    binance: &Account,
+    base: &str,
+    quote: &str,
+) -> Result<(Optimizer, Vec<Kline>), String> {
+    let start_time = options.start_time()?;
+    let end_time = options.end_time();
+
+    let mut optimizer = Optimizer::new(
+        base,
+        quote,
+        options.interval(),
+        start_time,
+        end_time,
+        options.metric,
+    )
+    .trend_filters(&options.trend_filters)
+    .risk(options.risk)
+    .sizing(options.sizing);
+    if let Some(threads) = options.threads {
+        optimizer = optimizer.threads(threads);
+    }
 
-    /*
     let mut backtester = Backtester::new(
         start_time,
         end_time,
-        Symbol::new("BNB", "USDT"),
-        Interval::Hour(1),
+        Symbol::new(base, quote).with_trend_filters(&options.trend_filters),
+        options.interval(),
+    )
+    .dir(&options.dir);
+    if let Some(source) = options.source_interval {
+        backtester = backtester.source(source);
+    }
+    let klines = backtester.load_klines(binance);
+
+    Ok((optimizer, klines))
+}
+
+fn optimize(options: Options) -> Result<(), String> {
+    if options.symbols.is_empty() {
+        return Err(String::from("`--symbols` is required"));
+    }
+
+    let binance = Account::new();
+    let candidates = candidates(&options);
+
+    for (base, quote) in options.symbols.iter() {
+        let (optimizer, klines) = optimizer(&options, &binance, base, quote)?;
+
+        println!(
+            "[INFO] Testing {} parameter sets on {}{} ({} klines), ranked by {}\n",
+            candidates.len(),
+            base,
+            quote,
+            klines.len(),
+            optimizer.metric()
+        );
+
+        let results = optimizer.run(&candidates, &klines);
+        for (rank, (parameters, report)) in results.iter().take(options.top).enumerate() {
+            println!("{:>3}. {}\n     {}", rank + 1, parameters, report);
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn walk_forward(options: Options) -> Result<(), String> {
+    if options.symbols.is_empty() {
+        return Err(String::from("`--symbols` is required"));
+    }
+
+    let binance = Account::new();
+    let candidates = candidates(&options);
+    let start_time = options.start_time()?;
+    let end_time = options.end_time();
+
+    for (base, quote) in options.symbols.iter() {
+        let (optimizer, klines) = optimizer(&options, &binance, base, quote)?;
+        let walk_forward = WalkForward::new(
+            optimizer,
+            Duration::days(options.in_sample_days),
+            Duration::days(options.out_of_sample_days),
+        );
+
+        if walk_forward.windows(start_time, end_time).is_empty() {
+            return Err(format!(
+                "The range is shorter than the {} day in-sample window",
+                options.in_sample_days
+            ));
+        }
+
+        println!(
+            "[INFO] Walk-forward of {}{} over {} parameter sets, ranked by {}\n",
+            base,
+            quote,
+            candidates.len(),
+            options.metric
+        );
+
+        let report = walk_forward.run(&candidates, &klines, start_time, end_time);
+        for result in report.windows.iter() {
+            let (start, end) = result.window.out_of_sample;
+            println!(
+                "[INFO] {} - {}: {}\n       In-sample:     {}\n       Out-of-sample: {}",
+                start.format("%Y-%m-%d"),
+                end.format("%Y-%m-%d"),
+                result.parameters,
+                result.in_sample,
+                result.out_of_sample
+            );
+        }
+
+        println!("\n[INFO] Out-of-sample: {}\n", report.out_of_sample);
+    }
+
+    Ok(())
+}
+
+fn download(options: Options) -> Result<(), String> {
+    if options.symbols.is_empty() || options.intervals.is_empty() {
+        return Err(String::from("`--symbols` and `--interval` are required"));
+    }
+
+    let downloader = Downloader::new(
+        options.dir.clone(),
+        options.symbol_strings(),
+        options.intervals.clone(),
+        options.start_time()?,
+        options.end_time(),
     );
-    backtester.run(binance);
-    */
+
+    match downloader.run(&Account::new()) {
+        0 => Ok(()),
+        failed => Err(format!(
+            "{} downloads failed, run again to resume them",
+            failed
+        )),
+    }
+}
+
+fn balance() -> Result<(), String> {
+    let binance = Account::new();
+    let buf = std::fs::read("cache.toml").map_err(|e| format!("cache.toml: {}", e))?;
+    let (assets, _) = TomlParser::new(&buf).load_cache();
+
+    for asset in assets.iter() {
+        let balance = binance
+            .get_balance(&asset.name)
+            .map_err(|e| format!("{}: {}", asset.name, e))?;
+        println!(
+            "{:>8}: {:.8} (cached {:.8})",
+            asset.name, balance, asset.balance
+        );
+    }
+
+    Ok(())
+}
+
+fn validate_config() -> Result<(), String> {
+    let mut errors = Vec::new();
+
+    match std::fs::read_to_string("config.txt") {
+        Ok(content) => {
+            let mut lines = content.lines();
+            for key in ["api_key:", "secret_key:"].iter() {
+                match lines.next().and_then(|line| line.strip_prefix(key)) {
+                    Some(value) if !value.trim().is_empty() => (),
+                    _ => errors.push(format!("config.txt: expected `{} <value>` line", key)),
+                }
+            }
+        }
+        Err(e) => errors.push(format!("config.txt: {}", e)),
+    }
+
+    match std::fs::read("cache.toml") {
+        Ok(buf) => {
+            let (assets, symbols) = TomlParser::new(&buf).load_cache();
+
+            if symbols.is_empty() {
+                errors.push(String::from("cache.toml: no symbols"));
+            }
+
+            for symbol in symbols.iter() {
+                for asset in [symbol.base(), symbol.quote()].iter() {
+                    if !assets.iter().any(|a| &a.name == asset) {
+                        errors.push(format!(
+                            "cache.toml: asset `{}` of {} is missing",
+                            asset,
+                            symbol.as_str()
+                        ));
+                    }
+                }
+            }
+
+            println!(
+                "[INFO] cache.toml: {} assets, {} symbols",
+                assets.len(),
+                symbols.len()
+            );
+        }
+        Err(e) => errors.push(format!("cache.toml: {}", e)),
+    }
+
+    if errors.is_empty() {
+        println!("[INFO] Configuration is valid");
+        Ok(())
+    } else {
+        Err(errors.join("\n[ERROR] "))
+    }
 }
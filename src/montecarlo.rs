@@ -0,0 +1,274 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::random::Rng;
+use crate::report::{self, Report, Trade};
+
+/// How the trades of a backtest are resampled for every simulated run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resample {
+    /// Same trades in a random order. The final equity never changes, only
+    /// the path to it.
+    Shuffle,
+    /// As many trades as the backtest, drawn with replacement.
+    Bootstrap,
+    /// Original order with every trade skipped at the given probability.
+    Skip(f64),
+}
+
+impl FromStr for Resample {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid resampling `{}`, expected shuffle, bootstrap or skip[:<RATE>]",
+                s
+            )
+        };
+
+        match s.split_once(':') {
+            None if s == "shuffle" => Ok(Resample::Shuffle),
+            None if s == "bootstrap" => Ok(Resample::Bootstrap),
+            None if s == "skip" => Ok(Resample::Skip(0.1f64)),
+            Some(("skip", rate)) => match rate.parse::<f64>() {
+                Ok(rate) if (0f64..1f64).contains(&rate) => Ok(Resample::Skip(rate)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Resample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resample::Shuffle => write!(f, "shuffle"),
+            Resample::Bootstrap => write!(f, "bootstrap"),
+            Resample::Skip(rate) => write!(f, "skip:{}", rate),
+        }
+    }
+}
+
+/// Sorted outcomes of the simulated runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution {
+    values: Vec<f64>,
+}
+
+impl Distribution {
+    pub fn new(mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        Self { values }
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.values.iter().sum::<f64>() / self.values.len() as f64
+    }
+
+    /// Nearest rank percentile, `p` in `[0, 100]`.
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.values.is_empty() {
+            return f64::NAN;
+        }
+
+        let rank = (p / 100f64 * self.values.len() as f64).ceil() as usize;
+        self.values[rank.clamp(1, self.values.len()) - 1]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MonteCarloReport {
+    pub resample: Resample,
+    pub initial_equity: f64,
+    pub final_equity: Distribution,
+    pub max_drawdown: Distribution,
+    /// Share of the runs that lost the ruin fraction of the initial equity.
+    pub risk_of_ruin: f64,
+}
+
+impl fmt::Display for MonteCarloReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let roi = |e: f64| (e / self.initial_equity - 1f64) * 100f64;
+
+        writeln!(
+            f,
+            "Monte Carlo ({}, {} runs)",
+            self.resample,
+            self.final_equity.values().len()
+        )?;
+        writeln!(
+            f,
+            "  ROI      5%: {:>7.1}%  50%: {:>7.1}%  95%: {:>7.1}%",
+            roi(self.final_equity.percentile(5f64)),
+            roi(self.final_equity.percentile(50f64)),
+            roi(self.final_equity.percentile(95f64)),
+        )?;
+        writeln!(
+            f,
+            "  Max DD  50%: {:>7.1}%  95%: {:>7.1}%  99%: {:>7.1}%",
+            self.max_drawdown.percentile(50f64) * 100f64,
+            self.max_drawdown.percentile(95f64) * 100f64,
+            self.max_drawdown.percentile(99f64) * 100f64,
+        )?;
+        write!(f, "  Risk of ruin: {:.1}%", self.risk_of_ruin * 100f64)
+    }
+}
+
+/// Resamples the trade sequence of a backtest many times to show how much of
+/// its result depends on the order and luck of individual trades.
+///
+/// Every run puts the fraction of the equity the
+/// [`Backtester`](crate::trading::Backtester) sized each trade with into it.
+pub struct MonteCarlo {
+    runs: usize,
+    resample: Resample,
+    ruin: f64,
+}
+
+impl MonteCarlo {
+    pub fn new(runs: usize, resample: Resample) -> Self {
+        Self {
+            runs: runs.max(1),
+            resample,
+            ruin: 0.5f64,
+        }
+    }
+
+    /// Counts a run as ruined once it has lost `fraction` of the initial
+    /// equity, one half by default.
+    pub fn ruin(mut self, fraction: f64) -> Self {
+        self.ruin = fraction;
+        self
+    }
+
+    pub fn run(&self, report: &Report, rng: &mut Rng) -> MonteCarloReport {
+        let initial = report.initial_equity;
+        let ruin_equity = initial * (1f64 - self.ruin);
+        let mut final_equity = Vec::with_capacity(self.runs);
+        let mut max_drawdown = Vec::with_capacity(self.runs);
+        let mut ruined = 0;
+
+        for _ in 0..self.runs {
+            let trades = self.resample(&report.trades, rng);
+            let equity = equity_curve(initial, &trades);
+
+            if equity.iter().any(|&e| e <= ruin_equity) {
+                ruined += 1;
+            }
+
+            final_equity.push(equity.last().copied().unwrap_or(initial));
+            max_drawdown.push(report::max_drawdown(initial, &equity));
+        }
+
+        MonteCarloReport {
+            resample: self.resample,
+            initial_equity: initial,
+            final_equity: Distribution::new(final_equity),
+            max_drawdown: Distribution::new(max_drawdown),
+            risk_of_ruin: ruined as f64 / self.runs as f64,
+        }
+    }
+
+    fn resample(&self, trades: &[Trade], rng: &mut Rng) -> Vec<Trade> {
+        match self.resample {
+            Resample::Shuffle => {
+                let mut trades = trades.to_vec();
+                rng.shuffle(&mut trades);
+                trades
+            }
+            Resample::Bootstrap if trades.is_empty() => Vec::new(),
+            Resample::Bootstrap => (0..trades.len())
+                .map(|_| trades[rng.below(trades.len())])
+                .collect(),
+            Resample::Skip(rate) => trades
+                .iter()
+                .filter(|_| rng.next_f64() >= rate)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+/// Equity after each trade when its fraction of the equity is put into it.
+fn equity_curve(initial: f64, trades: &[Trade]) -> Vec<f64> {
+    let mut equity = initial;
+
+    trades
+        .iter()
+        .map(|trade| {
+            equity *= 1f64 + trade.fraction * trade.profit();
+            equity
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(profits: &[f64]) -> Report {
+        Report {
+            trades: profits
+                .iter()
+                .map(|p| Trade {
+                    entry_time: 0,
+                    exit_time: 0,
+                    entry_price: 100f64,
+                    exit_price: 100f64 * (1f64 + p),
+                    fraction: 1f64,
+                })
+                .collect(),
+            initial_equity: 100f64,
+            ..Report::default()
+        }
+    }
+
+    #[test]
+    fn resampled_distributions() {
+        let report = report(&[0.1f64, -0.2f64, 0.15f64, -0.05f64, 0.3f64, -0.1f64]);
+        let expected = equity_curve(100f64, &report.trades).pop().unwrap();
+
+        let shuffled = MonteCarlo::new(200, Resample::Shuffle).run(&report, &mut Rng::new(1));
+        assert!(shuffled
+            .final_equity
+            .values()
+            .iter()
+            .all(|e| (e - expected).abs() < 1e-9));
+        assert!(shuffled.max_drawdown.percentile(0f64) < shuffled.max_drawdown.percentile(100f64));
+
+        let bootstrap = MonteCarlo::new(200, Resample::Bootstrap)
+            .ruin(0.3f64)
+            .run(&report, &mut Rng::new(1));
+        assert!(bootstrap.final_equity.percentile(5f64) < bootstrap.final_equity.percentile(95f64));
+        assert!(bootstrap.risk_of_ruin > 0f64 && bootstrap.risk_of_ruin < 1f64);
+
+        let skipped = MonteCarlo::new(10, Resample::Skip(0f64)).run(&report, &mut Rng::new(1));
+        assert_eq!(skipped.risk_of_ruin, 0f64);
+        assert!((skipped.final_equity.mean() - expected).abs() < 1e-9);
+
+        // Half the equity in a 10% gain and a 20% loss.
+        let mut half = report.clone();
+        half.trades.truncate(2);
+        half.trades.iter_mut().for_each(|t| t.fraction = 0.5f64);
+        let shuffled = MonteCarlo::new(10, Resample::Shuffle).run(&half, &mut Rng::new(1));
+        assert!((shuffled.final_equity.mean() - 100f64 * 1.05f64 * 0.9f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parse_resample_and_percentiles() {
+        assert_eq!("skip".parse::<Resample>().unwrap(), Resample::Skip(0.1f64));
+        assert_eq!("skip:0.25".parse::<Resample>(), Ok(Resample::Skip(0.25f64)));
+        assert!("skip:1".parse::<Resample>().is_err());
+        assert!("jackknife".parse::<Resample>().is_err());
+
+        let distribution = Distribution::new(vec![5f64, 1f64, 4f64, 2f64, 3f64]);
+        assert_eq!(distribution.percentile(0f64), 1f64);
+        assert_eq!(distribution.percentile(50f64), 3f64);
+        assert_eq!(distribution.percentile(100f64), 5f64);
+    }
+}
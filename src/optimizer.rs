@@ -0,0 +1,416 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use chrono::{DateTime, Utc};
+
+use crate::exchange::{Interval, Kline};
+use crate::indicators::ma::MaType;
+use crate::random::Rng;
+use crate::report::{Metric, Report};
+use crate::risk::RiskRules;
+use crate::sizing::PositionSizing;
+use crate::timeframe::TrendFilter;
+use crate::trading::{Backtester, Parameters, Symbol};
+
+/// Evenly spaced values of a single parameter, `min:max:step` on the command
+/// line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Axis {
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+}
+
+impl Axis {
+    pub fn single(value: f64) -> Self {
+        Self {
+            min: value,
+            max: value,
+            step: 1f64,
+        }
+    }
+
+    pub fn is_single(&self) -> bool {
+        self.count() == 1
+    }
+
+    /// Number of values on the axis.
+    pub fn count(&self) -> usize {
+        ((self.max - self.min) / self.step + 1e-9).floor() as usize + 1
+    }
+
+    pub fn value(&self, index: usize) -> f64 {
+        self.min + self.step * index as f64
+    }
+
+    pub fn values(&self) -> Vec<f64> {
+        (0..self.count()).map(|i| self.value(i)).collect()
+    }
+}
+
+impl FromStr for Axis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid value `{}`, expected <X> or <MIN>:<MAX>:<STEP>", s);
+        let numbers = s
+            .split(':')
+            .map(|n| n.parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        if numbers.iter().any(|n| !n.is_finite()) {
+            return Err(invalid());
+        }
+
+        match numbers[..] {
+            [value] => Ok(Axis::single(value)),
+            [min, max, step] if max >= min && step > 0f64 => Ok(Axis { min, max, step }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Search space of the strategy [`Parameters`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    pub dmi_period: Axis,
+    /// Not searched, every combination uses it.
+    pub adx_ma: MaType,
+    pub dema_period: Axis,
+    pub bb_period: Axis,
+    pub bb_multiplier: Axis,
+    pub adx_trend: Axis,
+    pub adx_strong: Axis,
+    pub adx_extreme: Axis,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            dmi_period: Axis {
+                min: 10f64,
+                max: 20f64,
+                step: 2f64,
+            },
+            adx_ma: MaType::Dema,
+            dema_period: Axis {
+                min: 5f64,
+                max: 13f64,
+                step: 2f64,
+            },
+            bb_period: Axis::single(20f64),
+            bb_multiplier: Axis {
+                min: 1.5f64,
+                max: 2.5f64,
+                step: 0.25f64,
+            },
+            adx_trend: Axis {
+                min: 10f64,
+                max: 20f64,
+                step: 5f64,
+            },
+            adx_strong: Axis {
+                min: 20f64,
+                max: 30f64,
+                step: 5f64,
+            },
+            adx_extreme: Axis {
+                min: 35f64,
+                max: 50f64,
+                step: 5f64,
+            },
+        }
+    }
+}
+
+impl Grid {
+    fn axes(&self) -> [Axis; 7] {
+        [
+            self.dmi_period,
+            self.dema_period,
+            self.bb_period,
+            self.bb_multiplier,
+            self.adx_trend,
+            self.adx_strong,
+            self.adx_extreme,
+        ]
+    }
+
+    /// Number of parameter combinations in the grid.
+    pub fn len(&self) -> usize {
+        self.axes().iter().map(Axis::count).product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn at(&self, indices: [usize; 7]) -> Parameters {
+        Parameters {
+            dmi_period: self.dmi_period.value(indices[0]).round() as usize,
+            adx_ma: self.adx_ma,
+            dema_period: self.dema_period.value(indices[1]).round() as usize,
+            bb_period: self.bb_period.value(indices[2]).round() as usize,
+            bb_multiplier: self.bb_multiplier.value(indices[3]),
+            adx_trend: self.adx_trend.value(indices[4]),
+            adx_strong: self.adx_strong.value(indices[5]),
+            adx_extreme: self.adx_extreme.value(indices[6]),
+        }
+    }
+
+    /// Every combination of the grid.
+    pub fn parameters(&self) -> Vec<Parameters> {
+        let axes = self.axes();
+        let mut indices = [0usize; 7];
+        let mut parameters = Vec::with_capacity(self.len());
+
+        'outer: loop {
+            parameters.push(self.at(indices));
+
+            for (i, axis) in axes.iter().enumerate() {
+                indices[i] += 1;
+                if indices[i] < axis.count() {
+                    continue 'outer;
+                }
+                indices[i] = 0;
+            }
+
+            break;
+        }
+
+        parameters
+    }
+
+    /// Up to `n` combinations drawn uniformly from the grid, each at
+    /// distinct axis indices. Periods rounded from fractional steps can still
+    /// make two of them equal.
+    pub fn sample(&self, rng: &mut Rng, n: usize) -> Vec<Parameters> {
+        if n >= self.len() {
+            return self.parameters();
+        }
+
+        let axes = self.axes();
+        let mut drawn = HashSet::with_capacity(n);
+        let mut parameters = Vec::with_capacity(n);
+
+        while parameters.len() < n {
+            let mut indices = [0usize; 7];
+            for (index, axis) in indices.iter_mut().zip(axes.iter()) {
+                *index = rng.below(axis.count());
+            }
+
+            if drawn.insert(indices) {
+                parameters.push(self.at(indices));
+            }
+        }
+
+        parameters
+    }
+}
+
+/// Runs the [`Backtester`] of a symbol over many parameter sets in parallel,
+/// sharing the same klines between all runs.
+#[derive(Clone)]
+pub struct Optimizer {
+    base: String,
+    quote: String,
+    interval: Interval,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    metric: Metric,
+    threads: usize,
+    trend_filters: Vec<TrendFilter>,
+    risk: RiskRules,
+    sizing: PositionSizing,
+}
+
+impl Optimizer {
+    pub fn new(
+        base: &str,
+        quote: &str,
+        interval: Interval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        metric: Metric,
+    ) -> Self {
+        Self {
+            base: base.to_string(),
+            quote: quote.to_string(),
+            interval,
+            start_time,
+            end_time,
+            metric,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            trend_filters: Vec::new(),
+            risk: RiskRules::default(),
+            sizing: PositionSizing::default(),
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Higher timeframe filters applied to every parameter set.
+    pub fn trend_filters(mut self, filters: &[TrendFilter]) -> Self {
+        self.trend_filters = filters.to_vec();
+        self
+    }
+
+    /// Risk rules applied to every parameter set.
+    pub fn risk(mut self, rules: RiskRules) -> Self {
+        self.risk = rules;
+        self
+    }
+
+    /// Position sizing of every parameter set.
+    pub fn sizing(mut self, sizing: PositionSizing) -> Self {
+        self.sizing = sizing;
+        self
+    }
+
+    /// The same optimizer over another date range.
+    pub fn with_range(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Self {
+        Self {
+            start_time,
+            end_time,
+            ..self.clone()
+        }
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    pub fn backtest(&self, parameters: Parameters, klines: &[Kline]) -> Report {
+        let symbol = Symbol::new(&self.base, &self.quote)
+            .with_parameters(parameters)
+            .with_trend_filters(&self.trend_filters)
+            .with_risk(self.risk)
+            .with_sizing(self.sizing);
+
+        Backtester::new(self.start_time, self.end_time, symbol, self.interval)
+            .quiet()
+            .run_klines(klines)
+    }
+
+    /// Backtests every candidate and returns them ranked best first by the
+    /// optimizer's metric, ties kept in the order of `candidates`.
+    pub fn run(&self, candidates: &[Parameters], klines: &[Kline]) -> Vec<(Parameters, Report)> {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(candidates.len()));
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(candidates.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let parameters = match candidates.get(i) {
+                        Some(&parameters) => parameters,
+                        None => break,
+                    };
+
+                    let report = self.backtest(parameters, klines);
+                    results.lock().unwrap().push((i, parameters, report));
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by(|(i, _, a), (j, _, b)| {
+            let (a, b) = (self.metric.score(a), self.metric.score(b));
+            b.partial_cmp(&a)
+                .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+                .then(i.cmp(j))
+        });
+
+        results
+            .into_iter()
+            .map(|(_, parameters, report)| (parameters, report))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axis_from_str() {
+        assert_eq!("2.5".parse::<Axis>().unwrap(), Axis::single(2.5f64));
+
+        let axis = "10:20:5".parse::<Axis>().unwrap();
+        assert_eq!(axis.values(), vec![10f64, 15f64, 20f64]);
+        assert_eq!("1.5:2.5:0.25".parse::<Axis>().unwrap().count(), 5);
+
+        assert!("20:10:5".parse::<Axis>().is_err());
+        assert!("10:20:0".parse::<Axis>().is_err());
+        assert!("10:20".parse::<Axis>().is_err());
+        assert!("1:inf:1".parse::<Axis>().is_err());
+        assert!("1:10:inf".parse::<Axis>().is_err());
+        assert!("nan:10:1".parse::<Axis>().is_err());
+    }
+
+    #[test]
+    fn grid_combinations() {
+        let grid = Grid {
+            dmi_period: "10:14:2".parse().unwrap(),
+            adx_ma: MaType::Dema,
+            dema_period: Axis::single(9f64),
+            bb_period: Axis::single(20f64),
+            bb_multiplier: "1:2:1".parse().unwrap(),
+            adx_trend: Axis::single(15f64),
+            adx_strong: Axis::single(25f64),
+            adx_extreme: Axis::single(40f64),
+        };
+
+        let parameters = grid.parameters();
+        assert_eq!(grid.len(), 6);
+        assert_eq!(parameters.len(), 6);
+        assert!(parameters
+            .iter()
+            .any(|p| p.dmi_period == 14 && p.bb_multiplier == 2f64));
+
+        for (i, p) in parameters.iter().enumerate() {
+            assert!(!parameters[i + 1..].contains(p));
+        }
+
+        let sample = grid.sample(&mut Rng::new(7), 4);
+        assert_eq!(sample.len(), 4);
+        assert!(sample.iter().all(|p| parameters.contains(p)));
+
+        // Steps of 0.4 round 10, 10.4 and 10.8 to two distinct periods, four
+        // distinct sets out of six combinations.
+        let rounded = Grid {
+            dmi_period: "10:10.8:0.4".parse().unwrap(),
+            ..grid
+        };
+        assert_eq!(rounded.len(), 6);
+        assert_eq!(rounded.sample(&mut Rng::new(7), 5).len(), 5);
+    }
+
+    #[test]
+    fn ties_keep_candidate_order() {
+        let optimizer = Optimizer::new(
+            "BNB",
+            "USDT",
+            Interval::Hour(1),
+            Utc::now(),
+            Utc::now(),
+            Metric::Roi,
+        )
+        .threads(4);
+        let candidates = Grid::default().parameters();
+
+        // Without klines nothing trades and every set scores the same.
+        let ranked: Vec<Parameters> = optimizer
+            .run(&candidates, &[])
+            .into_iter()
+            .map(|(parameters, _)| parameters)
+            .collect();
+        assert_eq!(ranked, candidates);
+    }
+}
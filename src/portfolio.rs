@@ -0,0 +1,322 @@
+//! Holdings across many assets, valued in a reference asset, with the
+//! realized and unrealized P&L of every symbol traded.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::exchange::binance::Asset;
+
+/// Last prices of the traded pairs, converting between any two assets
+/// connected by them.
+#[derive(Clone, Debug, Default)]
+pub struct Prices {
+    /// `base` to `quote` prices.
+    pairs: HashMap<(String, String), f64>,
+}
+
+impl Prices {
+    pub fn set(&mut self, base: &str, quote: &str, price: f64) {
+        self.pairs
+            .insert((base.to_string(), quote.to_string()), price);
+    }
+
+    /// Amount of `to` one `from` is worth, through the fewest pairs.
+    pub fn rate(&self, from: &str, to: &str) -> Option<f64> {
+        if from == to {
+            return Some(1f64);
+        }
+
+        let mut rates: HashMap<&str, f64> = HashMap::new();
+        let mut queue = VecDeque::new();
+        rates.insert(from, 1f64);
+        queue.push_back(from);
+
+        while let Some(asset) = queue.pop_front() {
+            let rate = rates[asset];
+
+            for ((base, quote), &price) in self.pairs.iter() {
+                let (next, next_rate) = if base == asset && price > 0f64 {
+                    (quote.as_str(), rate * price)
+                } else if quote == asset && price > 0f64 {
+                    (base.as_str(), rate / price)
+                } else {
+                    continue;
+                };
+
+                if next == to {
+                    return Some(next_rate);
+                }
+                if !rates.contains_key(next) {
+                    rates.insert(next, next_rate);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Position of one symbol, in its quote asset.
+#[derive(Clone, Debug, PartialEq)]
+struct Book {
+    symbol: String,
+    base: String,
+    quote: String,
+    quantity: f64,
+    /// Cost of `quantity`.
+    cost: f64,
+    realized: f64,
+}
+
+/// Realized and unrealized P&L of a symbol in the reference asset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pnl {
+    pub symbol: String,
+    pub realized: f64,
+    pub unrealized: f64,
+}
+
+impl Pnl {
+    pub fn total(&self) -> f64 {
+        self.realized + self.unrealized
+    }
+}
+
+pub struct Portfolio {
+    reference: String,
+    assets: Vec<Asset>,
+    prices: Prices,
+    books: Vec<Book>,
+}
+
+impl Portfolio {
+    /// Values everything in `reference`, e.g. USDT.
+    pub fn new(reference: &str) -> Self {
+        Self {
+            reference: reference.to_string(),
+            assets: Vec::new(),
+            prices: Prices::default(),
+            books: Vec::new(),
+        }
+    }
+
+    pub fn with_assets(mut self, assets: Vec<Asset>) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    pub fn set_reference(&mut self, reference: &str) {
+        self.reference = reference.to_string();
+    }
+
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    pub fn assets(&self) -> &[Asset] {
+        &self.assets
+    }
+
+    pub fn balance(&self, asset: &str) -> f64 {
+        self.assets
+            .iter()
+            .find(|a| a.name == asset)
+            .map_or(0f64, |a| a.balance)
+    }
+
+    /// Overwrites a balance, e.g. with the one the exchange reports.
+    pub fn set_balance(&mut self, asset: &str, balance: f64) {
+        match self.assets.iter_mut().find(|a| a.name == asset) {
+            Some(a) => a.balance = balance,
+            None => self.assets.push(Asset::new(asset, balance)),
+        }
+    }
+
+    pub fn set_price(&mut self, base: &str, quote: &str, price: f64) {
+        self.prices.set(base, quote, price);
+    }
+
+    fn book(&mut self, symbol: &str, base: &str, quote: &str) -> &mut Book {
+        match self.books.iter().position(|b| b.symbol == symbol) {
+            Some(i) => &mut self.books[i],
+            None => {
+                self.books.push(Book {
+                    symbol: symbol.to_string(),
+                    base: base.to_string(),
+                    quote: quote.to_string(),
+                    quantity: 0f64,
+                    cost: 0f64,
+                    realized: 0f64,
+                });
+                self.books.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Books `quantity` of `base` bought at `price` in `quote`, moving the
+    /// balances.
+    pub fn buy(&mut self, symbol: &str, base: &str, quote: &str, quantity: f64, price: f64) {
+        let book = self.book(symbol, base, quote);
+        book.quantity += quantity;
+        book.cost += quantity * price;
+
+        self.set_balance(base, self.balance(base) + quantity);
+        self.set_balance(quote, self.balance(quote) - quantity * price);
+        self.prices.set(base, quote, price);
+    }
+
+    /// Books `quantity` of `base` sold at `price`, realizing the P&L against
+    /// the average cost.
+    pub fn sell(&mut self, symbol: &str, base: &str, quote: &str, quantity: f64, price: f64) {
+        let book = self.book(symbol, base, quote);
+        let sold = quantity.min(book.quantity);
+        let cost = if book.quantity > 0f64 {
+            book.cost * sold / book.quantity
+        } else {
+            0f64
+        };
+
+        book.realized += sold * price - cost;
+        book.quantity -= sold;
+        book.cost -= cost;
+
+        self.set_balance(base, self.balance(base) - quantity);
+        self.set_balance(quote, self.balance(quote) + quantity * price);
+        self.prices.set(base, quote, price);
+    }
+
+    /// Worth of `amount` of `asset` in the reference asset.
+    pub fn value(&self, asset: &str, amount: f64) -> Option<f64> {
+        self.prices
+            .rate(asset, &self.reference)
+            .map(|rate| amount * rate)
+    }
+
+    /// Total worth of the holdings in `asset`, leaving out the ones that
+    /// can not be converted to it yet.
+    pub fn equity_in(&self, asset: &str) -> f64 {
+        self.assets
+            .iter()
+            .filter_map(|a| {
+                self.prices
+                    .rate(&a.name, asset)
+                    .map(|rate| a.balance * rate)
+            })
+            .sum()
+    }
+
+    /// Total worth of the holdings in the reference asset.
+    pub fn equity(&self) -> f64 {
+        self.equity_in(&self.reference)
+    }
+
+    /// Assets whose worth is unknown, missing from the equity.
+    pub fn unpriced(&self) -> Vec<&str> {
+        self.assets
+            .iter()
+            .filter(|a| a.balance != 0f64 && self.value(&a.name, a.balance).is_none())
+            .map(|a| a.name.as_str())
+            .collect()
+    }
+
+    /// P&L of every symbol traded in the reference asset, at the current
+    /// rate of its quote.
+    pub fn pnl(&self) -> Vec<Pnl> {
+        self.books
+            .iter()
+            .map(|book| {
+                let rate = self.prices.rate(&book.quote, &self.reference);
+                let price = self.prices.rate(&book.base, &book.quote);
+                let unrealized = price.map_or(0f64, |price| book.quantity * price - book.cost);
+
+                Pnl {
+                    symbol: book.symbol.clone(),
+                    realized: book.realized * rate.unwrap_or(0f64),
+                    unrealized: unrealized * rate.unwrap_or(0f64),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Portfolio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[INFO] Portfolio in {}:", self.reference)?;
+
+        for asset in self.assets.iter().filter(|a| a.balance != 0f64) {
+            match self.value(&asset.name, asset.balance) {
+                Some(value) => {
+                    writeln!(f, "    {}: {:.8} = {:.2}", asset.name, asset.balance, value)?
+                }
+                None => writeln!(f, "    {}: {:.8} = ?", asset.name, asset.balance)?,
+            }
+        }
+
+        let pnl = self.pnl();
+        for symbol in pnl.iter() {
+            writeln!(
+                f,
+                "    {}: Realized {:.2}  Unrealized {:.2}",
+                symbol.symbol, symbol.realized, symbol.unrealized
+            )?;
+        }
+
+        write!(
+            f,
+            "    Equity: {:.2}  P&L: {:.2}",
+            self.equity(),
+            pnl.iter().map(Pnl::total).sum::<f64>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn conversion_paths() {
+        let mut prices = Prices::default();
+        prices.set("BNB", "BTC", 0.01);
+        prices.set("BTC", "USDT", 30000f64);
+        prices.set("ETH", "BTC", 0.05);
+
+        assert_eq!(prices.rate("USDT", "USDT"), Some(1f64));
+        assert!(close(prices.rate("BNB", "USDT").unwrap(), 300f64));
+        assert!(close(prices.rate("USDT", "BTC").unwrap(), 1f64 / 30000f64));
+        assert!(close(prices.rate("BNB", "ETH").unwrap(), 0.2));
+        assert_eq!(prices.rate("BNB", "EUR"), None);
+    }
+
+    #[test]
+    fn pnl_across_quotes() {
+        let mut portfolio = Portfolio::new("USDT")
+            .with_assets(vec![Asset::new("USDT", 1000f64), Asset::new("BTC", 0.1)]);
+        portfolio.set_price("BTC", "USDT", 20000f64);
+        assert!(close(portfolio.equity(), 3000f64));
+
+        portfolio.buy("BNBBTC", "BNB", "BTC", 2f64, 0.01);
+        portfolio.buy("BNBBTC", "BNB", "BTC", 2f64, 0.02);
+        assert!(close(portfolio.balance("BTC"), 0.04));
+
+        // Half sold above the average cost of 0.015.
+        portfolio.sell("BNBBTC", "BNB", "BTC", 2f64, 0.025);
+        portfolio.set_price("BTC", "USDT", 30000f64);
+
+        let pnl = portfolio.pnl();
+        assert_eq!(pnl.len(), 1);
+        assert!(close(pnl[0].realized, 0.02 * 30000f64));
+        assert!(close(pnl[0].unrealized, 0.02 * 30000f64));
+        // 1000 USDT, 0.09 BTC and 2 BNB at 0.025 BTC.
+        assert!(close(portfolio.equity(), 1000f64 + 0.14 * 30000f64));
+        assert!(portfolio.unpriced().is_empty());
+
+        portfolio.set_balance("XRP", 10f64);
+        assert_eq!(portfolio.unpriced(), vec!["XRP"]);
+    }
+}
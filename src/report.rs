@@ -0,0 +1,237 @@
+use std::fmt;
+use std::str::FromStr;
+
+const YEAR_MILLIS: f64 = 365f64 * 24f64 * 60f64 * 60f64 * 1000f64;
+
+/// A closed round trip of the backtested strategy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trade {
+    pub entry_time: i64,
+    pub exit_time: i64,
+    pub entry_price: f64,
+    pub exit_price: f64,
+    /// Fraction of the equity put into the trade.
+    pub fraction: f64,
+}
+
+impl Trade {
+    /// Relative return of the trade, `0.05` for a 5% gain.
+    pub fn profit(&self) -> f64 {
+        self.exit_price / self.entry_price - 1f64
+    }
+}
+
+/// Outcome of a backtest: its closed trades and the equity at the close of
+/// every tested kline.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub trades: Vec<Trade>,
+    pub equity: Vec<f64>,
+    pub initial_equity: f64,
+    /// Interval of the equity samples in milliseconds, used to annualize.
+    pub period: i64,
+}
+
+impl Report {
+    pub fn final_equity(&self) -> f64 {
+        self.equity.last().copied().unwrap_or(self.initial_equity)
+    }
+
+    pub fn roi(&self) -> f64 {
+        self.final_equity() / self.initial_equity - 1f64
+    }
+
+    /// Largest relative decline from an equity peak, `0.2` for 20%.
+    pub fn max_drawdown(&self) -> f64 {
+        max_drawdown(self.initial_equity, &self.equity)
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        if self.trades.is_empty() {
+            return 0f64;
+        }
+
+        let wins = self.trades.iter().filter(|t| t.profit() > 0f64).count();
+        wins as f64 / self.trades.len() as f64
+    }
+
+    /// Sum of winning trade returns over the sum of losing trade returns.
+    pub fn profit_factor(&self) -> f64 {
+        let (gains, losses) =
+            self.trades
+                .iter()
+                .map(Trade::profit)
+                .fold((0f64, 0f64), |(gains, losses), p| {
+                    if p > 0f64 {
+                        (gains + p, losses)
+                    } else {
+                        (gains, losses - p)
+                    }
+                });
+
+        if losses > 0f64 {
+            gains / losses
+        } else if gains > 0f64 {
+            f64::INFINITY
+        } else {
+            0f64
+        }
+    }
+
+    /// Annualized Sharpe ratio of the per kline equity returns, assuming a
+    /// zero risk free rate.
+    pub fn sharpe(&self) -> f64 {
+        let mut prev = self.initial_equity;
+        let returns: Vec<f64> = self
+            .equity
+            .iter()
+            .map(|&e| {
+                let r = e / prev - 1f64;
+                prev = e;
+                r
+            })
+            .collect();
+
+        if returns.len() < 2 || self.period <= 0 {
+            return 0f64;
+        }
+
+        let n = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / n;
+        let std = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1f64)).sqrt();
+
+        if std > 0f64 {
+            mean / std * (YEAR_MILLIS / self.period as f64).sqrt()
+        } else {
+            0f64
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ROI: {:.1}%  Max DD: {:.1}%  Sharpe: {:.2}  Trades: {}  Win Rate: {:.1}%  PF: {:.2}",
+            self.roi() * 100f64,
+            self.max_drawdown() * 100f64,
+            self.sharpe(),
+            self.trades.len(),
+            self.win_rate() * 100f64,
+            self.profit_factor(),
+        )
+    }
+}
+
+pub fn max_drawdown(initial: f64, equity: &[f64]) -> f64 {
+    let mut peak = initial;
+    let mut drawdown = 0f64;
+
+    for &e in equity {
+        peak = peak.max(e);
+        drawdown = drawdown.max((peak - e) / peak);
+    }
+
+    drawdown
+}
+
+/// Ranking criteria for backtest reports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    Roi,
+    Sharpe,
+    MaxDrawdown,
+    WinRate,
+    ProfitFactor,
+}
+
+impl Metric {
+    /// Scores a report so that higher is always better.
+    pub fn score(&self, report: &Report) -> f64 {
+        match self {
+            Metric::Roi => report.roi(),
+            Metric::Sharpe => report.sharpe(),
+            Metric::MaxDrawdown => -report.max_drawdown(),
+            Metric::WinRate => report.win_rate(),
+            Metric::ProfitFactor => report.profit_factor(),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "roi" => Ok(Metric::Roi),
+            "sharpe" => Ok(Metric::Sharpe),
+            "drawdown" => Ok(Metric::MaxDrawdown),
+            "win-rate" => Ok(Metric::WinRate),
+            "profit-factor" => Ok(Metric::ProfitFactor),
+            _ => Err(format!(
+                "Invalid metric `{}`, expected roi, sharpe, drawdown, win-rate or profit-factor",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Roi => write!(f, "roi"),
+            Metric::Sharpe => write!(f, "sharpe"),
+            Metric::MaxDrawdown => write!(f, "drawdown"),
+            Metric::WinRate => write!(f, "win-rate"),
+            Metric::ProfitFactor => write!(f, "profit-factor"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(entry_price: f64, exit_price: f64) -> Trade {
+        Trade {
+            entry_time: 0,
+            exit_time: 0,
+            entry_price,
+            exit_price,
+            fraction: 1f64,
+        }
+    }
+
+    #[test]
+    fn report_metrics() {
+        let report = Report {
+            trades: vec![
+                trade(100f64, 120f64),
+                trade(120f64, 90f64),
+                trade(90f64, 99f64),
+            ],
+            equity: vec![110f64, 120f64, 90f64, 99f64],
+            initial_equity: 100f64,
+            period: 60 * 60 * 1000,
+        };
+
+        assert!((report.roi() + 0.01f64).abs() < 1e-12);
+        assert!((report.max_drawdown() - 0.25f64).abs() < 1e-12);
+        assert!((report.win_rate() - 2f64 / 3f64).abs() < 1e-12);
+        assert!((report.profit_factor() - 0.3f64 / 0.25f64).abs() < 1e-12);
+        assert!(Metric::MaxDrawdown.score(&report) < 0f64);
+    }
+
+    #[test]
+    fn empty_report() {
+        let report = Report {
+            initial_equity: 100f64,
+            ..Report::default()
+        };
+
+        assert_eq!(report.roi(), 0f64);
+        assert_eq!(report.max_drawdown(), 0f64);
+        assert_eq!(report.sharpe(), 0f64);
+        assert_eq!(report.profit_factor(), 0f64);
+    }
+}
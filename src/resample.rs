@@ -0,0 +1,163 @@
+//! Builds klines of a higher interval out of the klines of a lower one, on
+//! the UTC boundaries of `Interval::open_time`.
+
+use crate::exchange::{Interval, Kline};
+
+const DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// Merges `from` klines into `to` klines: the first open, the highest high,
+/// the lowest low, the last close and the total volume.
+pub struct Resampler {
+    from: Interval,
+    to: Interval,
+    /// The kline in progress, `None` until one starts on a `to` boundary so
+    /// that a partial first kline is never returned.
+    kline: Option<Kline>,
+    close_time: i64,
+}
+
+impl Resampler {
+    pub fn new(from: Interval, to: Interval) -> Result<Self, String> {
+        let from_millis = from.to_millis();
+        let fits = match to {
+            // Days start on hour boundaries, so do weeks and months.
+            Interval::Week | Interval::Month => DAY % from_millis == 0,
+            _ => to.to_millis() > from_millis && to.to_millis() % from_millis == 0,
+        };
+
+        if !fits || from == to {
+            return Err(format!("Cannot build {} klines out of {} klines", to, from));
+        }
+
+        Ok(Self {
+            from,
+            to,
+            kline: None,
+            close_time: 0,
+        })
+    }
+
+    /// Adds the next `from` kline, returns the `to` kline it completes.
+    ///
+    /// A `to` kline missing its last klines, because of a gap in the data,
+    /// is returned when a kline of a later one comes in.
+    pub fn next(&mut self, kline: &Kline) -> Option<Kline> {
+        let open_time = self.to.open_time(kline.open_time);
+        let mut done = None;
+
+        match self.kline.as_mut() {
+            Some(current) if current.open_time == open_time => {
+                current.high = current.high.max(kline.high);
+                current.low = current.low.min(kline.low);
+                current.close = kline.close;
+                current.volume += kline.volume;
+            }
+            _ => {
+                done = self.kline.take();
+
+                if kline.open_time == open_time {
+                    self.kline = Some(Kline {
+                        open_time,
+                        ..kline.clone()
+                    });
+                    self.close_time = self.to.next_open_time(open_time);
+                }
+            }
+        }
+
+        if self.from.next_open_time(kline.open_time) == self.close_time {
+            return done.or_else(|| self.kline.take());
+        }
+
+        done
+    }
+
+    /// The kline in progress, if any.
+    pub fn partial(&self) -> Option<&Kline> {
+        self.kline.as_ref()
+    }
+}
+
+/// Resamples a whole history of `from` klines.
+pub fn resample(klines: &[Kline], from: Interval, to: Interval) -> Result<Vec<Kline>, String> {
+    let mut resampler = Resampler::new(from, to)?;
+    Ok(klines.iter().filter_map(|k| resampler.next(k)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn time(y: i32, m: u32, d: u32, h: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    fn hourly(start: i64, hours: i64) -> Vec<Kline> {
+        (0..hours)
+            .map(|i| {
+                let x = i as f64;
+                Kline {
+                    open_time: start + i * 3_600_000,
+                    open: x,
+                    high: x + 2f64,
+                    low: x - 1f64,
+                    close: x + 1f64,
+                    volume: 1f64,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ohlcv_of_higher_klines() {
+        // Starts at 02:00, the first 4h kline is partial and dropped.
+        let klines = hourly(time(2021, 3, 17, 2), 12);
+        let resampled = resample(&klines, Interval::Hour(1), Interval::Hour(4)).unwrap();
+
+        assert_eq!(resampled.len(), 2);
+        assert_eq!(
+            resampled[0],
+            Kline {
+                open_time: time(2021, 3, 17, 4),
+                open: 2f64,
+                high: 7f64,
+                low: 1f64,
+                close: 6f64,
+                volume: 4f64,
+            }
+        );
+
+        // A month of hours, from the 1st of February.
+        let klines = hourly(time(2021, 2, 1, 0), 28 * 24 + 1);
+        let months = resample(&klines, Interval::Hour(1), Interval::Month).unwrap();
+        assert_eq!(months.len(), 1);
+        assert_eq!(months[0].volume, 28f64 * 24f64);
+
+        assert!(Resampler::new(Interval::Hour(4), Interval::Hour(6)).is_err());
+        assert!(Resampler::new(Interval::Day(3), Interval::Week).is_err());
+        assert!(Resampler::new(Interval::Hour(1), Interval::Hour(1)).is_err());
+    }
+
+    #[test]
+    fn gaps() {
+        let mut klines = hourly(time(2021, 3, 17, 0), 12);
+        // No klines from 02:00 to 05:00, nor from 08:00 on.
+        klines.drain(2..6);
+        klines.truncate(6);
+
+        let mut resampler = Resampler::new(Interval::Hour(1), Interval::Hour(4)).unwrap();
+        let resampled: Vec<_> = klines.iter().filter_map(|k| resampler.next(k)).collect();
+
+        // 00:00 missing its end is returned with the kline at 06:00, the 4h
+        // kline that one belongs to started in the gap and is dropped.
+        assert_eq!(resampled.len(), 1);
+        assert_eq!(resampled[0].close, 2f64);
+        assert_eq!(
+            resampler.partial().map(|k| k.open_time),
+            Some(time(2021, 3, 17, 8))
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Exits applied on top of the strategy signals: stop-losses, trailing stops,
+//! take-profit targets and a maximum holding time.
+//!
+//! Rules are checked on closed klines and exit at the close, the only price
+//! the live trader can act on, so that backtests see the same exits.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::exchange::Kline;
+use crate::indicators::Atr;
+
+/// Distance from a price, `5%` or `2atr` on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distance {
+    /// Fraction of the price.
+    Percent(f64),
+    /// Multiple of the ATR at the entry.
+    Atr(f64),
+}
+
+impl FromStr for Distance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid distance `{}`, expected e.g. 5% or 2atr", s);
+
+        let (value, distance): (&str, fn(f64) -> Distance) =
+            if let Some(value) = s.strip_suffix('%') {
+                (value, |x| Distance::Percent(x / 100f64))
+            } else if let Some(value) = s.strip_suffix("atr") {
+                (value, Distance::Atr)
+            } else {
+                return Err(invalid());
+            };
+
+        match value.parse::<f64>() {
+            Ok(x) if x > 0f64 && x.is_finite() => Ok(distance(x)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Distance::Percent(x) => write!(f, "{}%", x * 100f64),
+            Distance::Atr(x) => write!(f, "{} ATR", x),
+        }
+    }
+}
+
+impl Distance {
+    /// Price distance from `price`, `None` for ATR multiples while the ATR
+    /// warms up.
+    fn price_distance(self, price: f64, atr: Option<f64>) -> Option<f64> {
+        match self {
+            Distance::Percent(x) => Some(price * x),
+            Distance::Atr(x) => atr.map(|atr| atr * x),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RiskRules {
+    /// Below the entry price.
+    pub stop_loss: Option<Distance>,
+    /// Below the highest close since the entry.
+    pub trailing_stop: Option<Distance>,
+    /// Above the entry price.
+    pub take_profit: Option<Distance>,
+    /// Klines after the entry.
+    pub max_holding: Option<usize>,
+    pub atr_period: usize,
+}
+
+impl Default for RiskRules {
+    fn default() -> Self {
+        Self {
+            stop_loss: None,
+            trailing_stop: None,
+            take_profit: None,
+            max_holding: None,
+            atr_period: 14,
+        }
+    }
+}
+
+impl RiskRules {
+    pub fn is_empty(&self) -> bool {
+        self.stop_loss.is_none()
+            && self.trailing_stop.is_none()
+            && self.take_profit.is_none()
+            && self.max_holding.is_none()
+    }
+}
+
+impl fmt::Display for RiskRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rules = Vec::new();
+
+        if let Some(distance) = self.stop_loss {
+            rules.push(format!("Stop Loss: {}", distance));
+        }
+        if let Some(distance) = self.trailing_stop {
+            rules.push(format!("Trailing Stop: {}", distance));
+        }
+        if let Some(distance) = self.take_profit {
+            rules.push(format!("Take Profit: {}", distance));
+        }
+        if let Some(klines) = self.max_holding {
+            rules.push(format!("Max Holding: {} klines", klines));
+        }
+
+        if rules.is_empty() {
+            write!(f, "None")
+        } else {
+            write!(f, "{}", rules.join("  "))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exit {
+    StopLoss,
+    TrailingStop,
+    TakeProfit,
+    MaxHolding,
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Exit::StopLoss => "Stop Loss",
+            Exit::TrailingStop => "Trailing Stop",
+            Exit::TakeProfit => "Take Profit",
+            Exit::MaxHolding => "Max Holding",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Levels of the open position.
+struct Entry {
+    stop_loss: Option<f64>,
+    take_profit: Option<f64>,
+    /// Trailing stop distance, fixed at the entry.
+    trailing: Option<f64>,
+    highest: f64,
+    klines: usize,
+}
+
+/// Tracks the position of one symbol against its `RiskRules`.
+pub struct Risk {
+    rules: RiskRules,
+    atr: Atr,
+    entry: Option<Entry>,
+}
+
+impl Default for Risk {
+    fn default() -> Self {
+        Self::new(RiskRules::default())
+    }
+}
+
+impl Risk {
+    pub fn new(rules: RiskRules) -> Self {
+        Self {
+            rules,
+            atr: Atr::new(rules.atr_period),
+            entry: None,
+        }
+    }
+
+    /// Adds a closed kline, before checking it.
+    pub fn update(&mut self, kline: &Kline, prev_kline: &Kline) {
+        self.atr.next(kline.high, prev_kline.close, kline.low);
+
+        if let Some(entry) = self.entry.as_mut() {
+            entry.highest = entry.highest.max(kline.close);
+            entry.klines += 1;
+        }
+    }
+
+    /// Whether every level of the rules can be set now, levels given in ATR
+    /// multiples need the ATR to be warmed up. Entries are refused until then.
+    pub fn is_ready(&self) -> bool {
+        let needs_atr = [
+            self.rules.stop_loss,
+            self.rules.trailing_stop,
+            self.rules.take_profit,
+        ]
+        .iter()
+        .any(|distance| matches!(distance, Some(Distance::Atr(_))));
+
+        !needs_atr || self.atr.get().is_some()
+    }
+
+    /// Opens a position at `price`. Levels given in ATR multiples are left
+    /// out if the ATR is not known yet, see [`Risk::is_ready`].
+    pub fn enter(&mut self, price: f64) {
+        let atr = self.atr.get();
+        let distance =
+            |distance: Option<Distance>| distance.and_then(|d| d.price_distance(price, atr));
+
+        self.entry = Some(Entry {
+            stop_loss: distance(self.rules.stop_loss).map(|d| price - d),
+            take_profit: distance(self.rules.take_profit).map(|d| price + d),
+            trailing: distance(self.rules.trailing_stop),
+            highest: price,
+            klines: 0,
+        });
+    }
+
+    pub fn exit(&mut self) {
+        self.entry = None;
+    }
+
+    pub fn atr(&self) -> Option<f64> {
+        self.atr.get()
+    }
+
+    /// The rule the last close breaks, if a position is open. Stops come
+    /// first when several are hit at once.
+    pub fn check(&self, kline: &Kline) -> Option<Exit> {
+        let entry = self.entry.as_ref()?;
+        let close = kline.close;
+
+        if matches!(entry.stop_loss, Some(stop) if close <= stop) {
+            Some(Exit::StopLoss)
+        } else if matches!(entry.trailing, Some(d) if close <= entry.highest - d) {
+            Some(Exit::TrailingStop)
+        } else if matches!(entry.take_profit, Some(target) if close >= target) {
+            Some(Exit::TakeProfit)
+        } else if matches!(self.rules.max_holding, Some(max) if entry.klines >= max) {
+            Some(Exit::MaxHolding)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline(close: f64) -> Kline {
+        Kline {
+            open: close,
+            high: close + 1f64,
+            low: close - 1f64,
+            close,
+            ..Kline::default()
+        }
+    }
+
+    /// Exits of a position entered at the first close.
+    fn run(rules: RiskRules, closes: &[f64]) -> Option<(usize, Exit)> {
+        let mut risk = Risk::new(rules);
+        let mut prev = kline(closes[0]);
+        for _ in 0..rules.atr_period {
+            risk.update(&prev, &prev);
+        }
+        risk.enter(closes[0]);
+
+        for (i, &close) in closes.iter().enumerate().skip(1) {
+            let kline = kline(close);
+            risk.update(&kline, &prev);
+            if let Some(exit) = risk.check(&kline) {
+                return Some((i, exit));
+            }
+            prev = kline;
+        }
+        None
+    }
+
+    #[test]
+    fn parse_distance() {
+        assert_eq!("5%".parse::<Distance>().unwrap(), Distance::Percent(0.05));
+        assert_eq!("2.5atr".parse::<Distance>().unwrap(), Distance::Atr(2.5));
+        assert!("5".parse::<Distance>().is_err());
+        assert!("-1%".parse::<Distance>().is_err());
+    }
+
+    #[test]
+    fn exits() {
+        let closes = [100f64, 104f64, 110f64, 106f64, 94f64];
+
+        let stop = RiskRules {
+            stop_loss: Some(Distance::Percent(0.05)),
+            ..RiskRules::default()
+        };
+        assert_eq!(run(stop, &closes), Some((4, Exit::StopLoss)));
+
+        // The ATR of klines 2 wide is 2, the stop trails 4 below 110.
+        let trailing = RiskRules {
+            trailing_stop: Some(Distance::Atr(2f64)),
+            ..stop
+        };
+        assert_eq!(run(trailing, &closes), Some((3, Exit::TrailingStop)));
+
+        let target = RiskRules {
+            take_profit: Some(Distance::Percent(0.1)),
+            ..stop
+        };
+        assert_eq!(run(target, &closes), Some((2, Exit::TakeProfit)));
+
+        let holding = RiskRules {
+            max_holding: Some(2),
+            ..RiskRules::default()
+        };
+        assert_eq!(run(holding, &closes), Some((2, Exit::MaxHolding)));
+        assert_eq!(run(RiskRules::default(), &closes), None);
+    }
+
+    #[test]
+    fn cold_atr() {
+        let rules = RiskRules {
+            stop_loss: Some(Distance::Percent(0.05)),
+            trailing_stop: Some(Distance::Atr(2f64)),
+            atr_period: 3,
+            ..RiskRules::default()
+        };
+        let mut risk = Risk::new(rules);
+        let kline = kline(100f64);
+
+        assert!(Risk::new(RiskRules::default()).is_ready());
+        let percent = RiskRules {
+            trailing_stop: None,
+            ..rules
+        };
+        assert!(Risk::new(percent).is_ready());
+
+        for _ in 0..2 {
+            assert!(!risk.is_ready());
+            risk.update(&kline, &kline);
+        }
+        assert!(!risk.is_ready());
+        risk.update(&kline, &kline);
+        assert!(risk.is_ready());
+    }
+}
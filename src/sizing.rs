@@ -0,0 +1,277 @@
+//! Quote amount to spend on each entry.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Closed trades needed before Kelly sizing trusts the win rate.
+const KELLY_MIN_TRADES: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sizing {
+    /// The free balance split evenly over the symbols without a position.
+    Even,
+    /// Fraction of the equity.
+    Fraction(f64),
+    /// Fixed quote amount.
+    Quote(f64),
+    /// Loses `risk` of the equity if the price falls `atr` ATRs.
+    Volatility { risk: f64, atr: f64 },
+    /// Kelly fraction of the equity from the closed trades, at most `cap`.
+    Kelly { cap: f64 },
+}
+
+impl FromStr for Sizing {
+    type Err = String;
+
+    /// `even`, `fraction:<X>`, `quote:<AMOUNT>`, `volatility:<RISK>[:<ATR>]`
+    /// or `kelly[:<CAP>]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid position sizing `{}`", s);
+        let mut parts = s.split(':');
+        let method = parts.next().unwrap_or_default().to_lowercase();
+        let values = parts
+            .map(|x| match x.parse::<f64>() {
+                Ok(x) if x > 0f64 && x.is_finite() => Ok(x),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let fraction = |x: f64| if x <= 1f64 { Ok(x) } else { Err(invalid()) };
+
+        match (method.as_str(), values.as_slice()) {
+            ("even", []) => Ok(Sizing::Even),
+            ("fraction", &[x]) => Ok(Sizing::Fraction(fraction(x)?)),
+            ("quote", &[amount]) => Ok(Sizing::Quote(amount)),
+            ("volatility", &[risk]) => Ok(Sizing::Volatility {
+                risk: fraction(risk)?,
+                atr: 2f64,
+            }),
+            ("volatility", &[risk, atr]) => Ok(Sizing::Volatility {
+                risk: fraction(risk)?,
+                atr,
+            }),
+            ("kelly", []) => Ok(Sizing::Kelly { cap: 0.25 }),
+            ("kelly", &[cap]) => Ok(Sizing::Kelly {
+                cap: fraction(cap)?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Sizing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sizing::Even => write!(f, "Even"),
+            Sizing::Fraction(x) => write!(f, "Fraction {}", x),
+            Sizing::Quote(amount) => write!(f, "Quote {}", amount),
+            Sizing::Volatility { risk, atr } => write!(f, "Volatility {} per {} ATR", risk, atr),
+            Sizing::Kelly { cap } => write!(f, "Kelly capped at {}", cap),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionSizing {
+    pub sizing: Sizing,
+    /// Largest fraction of the equity in one symbol.
+    pub max_allocation: Option<f64>,
+}
+
+impl Default for PositionSizing {
+    fn default() -> Self {
+        Self {
+            sizing: Sizing::Even,
+            max_allocation: None,
+        }
+    }
+}
+
+impl fmt::Display for PositionSizing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.sizing)?;
+        if let Some(max) = self.max_allocation {
+            write!(f, ", at most {} per symbol", max)?;
+        }
+        Ok(())
+    }
+}
+
+/// What an entry can spend, in the quote asset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Budget {
+    /// Free quote balance.
+    pub free: f64,
+    /// Free balance plus the open positions in the same quote.
+    pub equity: f64,
+    /// Symbols without a position, the one entering included.
+    pub slots: usize,
+    /// Smallest order the exchange accepts.
+    pub min_order: f64,
+}
+
+/// Sizes the entries of one symbol, keeping its closed trades for Kelly.
+pub struct Sizer {
+    sizing: PositionSizing,
+    wins: usize,
+    losses: usize,
+    /// Sums of the returns of the winning and losing trades.
+    won: f64,
+    lost: f64,
+}
+
+impl Default for Sizer {
+    fn default() -> Self {
+        Self::new(PositionSizing::default())
+    }
+}
+
+impl Sizer {
+    pub fn new(sizing: PositionSizing) -> Self {
+        Self {
+            sizing,
+            wins: 0,
+            losses: 0,
+            won: 0f64,
+            lost: 0f64,
+        }
+    }
+
+    /// Adds the return of a closed trade, `0.05` for 5%.
+    pub fn record(&mut self, trade_return: f64) {
+        if trade_return > 0f64 {
+            self.wins += 1;
+            self.won += trade_return;
+        } else {
+            self.losses += 1;
+            self.lost -= trade_return;
+        }
+    }
+
+    /// Kelly fraction `W - (1 - W) / R` of the closed trades, `None` until
+    /// there are enough of them.
+    pub fn kelly(&self) -> Option<f64> {
+        let trades = self.wins + self.losses;
+        if trades < KELLY_MIN_TRADES {
+            return None;
+        }
+
+        let win_rate = self.wins as f64 / trades as f64;
+        if self.wins == 0 || self.lost == 0f64 {
+            return Some(win_rate);
+        }
+
+        let payoff = (self.won / self.wins as f64) / (self.lost / self.losses as f64);
+        Some(win_rate - (1f64 - win_rate) / payoff)
+    }
+
+    /// Quote amount to buy at `price`, never more than the free balance.
+    /// Volatility sizing spends nothing while the ATR warms up.
+    pub fn size(&self, budget: &Budget, price: f64, atr: Option<f64>) -> f64 {
+        let amount = match self.sizing.sizing {
+            Sizing::Even => (1..=budget.slots.max(1))
+                .rev()
+                .map(|slots| budget.free / slots as f64)
+                .find(|&amount| amount > budget.min_order)
+                .unwrap_or(budget.free),
+            Sizing::Fraction(x) => budget.equity * x,
+            Sizing::Quote(amount) => amount,
+            Sizing::Volatility { risk, atr: m } => match atr {
+                Some(atr) if atr > 0f64 => budget.equity * risk / (m * atr) * price,
+                _ => 0f64,
+            },
+            // Half the cap while the trades are too few to tell.
+            Sizing::Kelly { cap } => {
+                budget.equity * self.kelly().map_or(cap / 2f64, |f| f.clamp(0f64, cap))
+            }
+        };
+
+        let max = self
+            .sizing
+            .max_allocation
+            .map_or(f64::INFINITY, |max| budget.equity * max);
+
+        amount.min(max).min(budget.free)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(free: f64, slots: usize) -> Budget {
+        Budget {
+            free,
+            equity: 1000f64,
+            slots,
+            min_order: 10f64,
+        }
+    }
+
+    fn sizer(s: &str) -> Sizer {
+        Sizer::new(PositionSizing {
+            sizing: s.parse().unwrap(),
+            max_allocation: None,
+        })
+    }
+
+    #[test]
+    fn parse_sizing() {
+        assert_eq!("even".parse::<Sizing>().unwrap(), Sizing::Even);
+        assert_eq!(
+            "volatility:0.01".parse::<Sizing>().unwrap(),
+            Sizing::Volatility {
+                risk: 0.01,
+                atr: 2f64
+            }
+        );
+        assert_eq!(
+            "kelly".parse::<Sizing>().unwrap(),
+            Sizing::Kelly { cap: 0.25 }
+        );
+        assert!("fraction:1.5".parse::<Sizing>().is_err());
+        assert!("quote".parse::<Sizing>().is_err());
+        assert!("even:1".parse::<Sizing>().is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        // Falls back to fewer slots while a share is below the minimum order.
+        assert_eq!(sizer("even").size(&budget(100f64, 4), 1f64, None), 25f64);
+        assert_eq!(sizer("even").size(&budget(30f64, 4), 1f64, None), 15f64);
+
+        assert_eq!(
+            sizer("fraction:0.1").size(&budget(500f64, 1), 1f64, None),
+            100f64
+        );
+        assert_eq!(
+            sizer("quote:700").size(&budget(500f64, 1), 1f64, None),
+            500f64
+        );
+
+        // 1% of 1000 lost over 2 ATRs of 5 is 1 base unit at a price of 50.
+        let volatility = sizer("volatility:0.01:2");
+        assert_eq!(
+            volatility.size(&budget(500f64, 1), 50f64, Some(5f64)),
+            50f64
+        );
+        assert_eq!(volatility.size(&budget(500f64, 1), 50f64, None), 0f64);
+
+        let mut capped = Sizer::new(PositionSizing {
+            sizing: Sizing::Fraction(0.5),
+            max_allocation: Some(0.2),
+        });
+        assert_eq!(capped.size(&budget(800f64, 1), 1f64, None), 200f64);
+
+        // 60% won 10%, 40% lost 5%: 0.6 - 0.4 / 2.
+        for i in 0..10 {
+            capped.record(if i < 6 { 0.1 } else { -0.05 });
+        }
+        assert!((capped.kelly().unwrap() - 0.4).abs() < 1e-9);
+        let mut kelly = sizer("kelly:0.25");
+        assert_eq!(kelly.size(&budget(800f64, 1), 1f64, None), 125f64);
+        for i in 0..10 {
+            kelly.record(if i < 6 { 0.1 } else { -0.05 });
+        }
+        assert_eq!(kelly.size(&budget(800f64, 1), 1f64, None), 250f64);
+    }
+}
@@ -0,0 +1,400 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::exchange::binance::{self, Account};
+use crate::exchange::{Interval, Kline};
+
+const MAGIC: &[u8; 4] = b"TRKL";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 5;
+const RECORD_SIZE: usize = 48;
+pub const PAGE_LIMIT: u32 = 1000;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Binance(binance::Error),
+    Corrupted(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Binance(e) => write!(f, "{}", e),
+            Error::Corrupted(msg) => write!(f, "Corrupted kline store: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<binance::Error> for Error {
+    fn from(e: binance::Error) -> Self {
+        Error::Binance(e)
+    }
+}
+
+/// Local candle history of a single symbol and interval.
+///
+/// Klines are kept sorted by open time without duplicates and persisted in a
+/// compact little-endian binary file at `{dir}/{symbol}_{interval}.bin`.
+pub struct KlineStore {
+    path: PathBuf,
+    symbol: String,
+    interval: Interval,
+    klines: Vec<Kline>,
+    duplicates: usize,
+}
+
+impl KlineStore {
+    pub fn open<P: AsRef<Path>>(dir: P, symbol: &str, interval: Interval) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(format!("{}_{}.bin", symbol, interval));
+
+        let mut klines = match fs::read(&path) {
+            Ok(bytes) => decode(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let duplicates = normalize(&mut klines);
+
+        Ok(Self {
+            path,
+            symbol: symbol.to_string(),
+            interval,
+            klines,
+            duplicates,
+        })
+    }
+
+    /// Number of duplicate klines dropped from the file when it was opened.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn interval(&self) -> Interval {
+        self.interval
+    }
+
+    pub fn klines(&self) -> &[Kline] {
+        &self.klines
+    }
+
+    /// Returns the stored klines whose open time lies in `[start, end]`.
+    pub fn range(&self, start: i64, end: i64) -> &[Kline] {
+        let from = self.klines.partition_point(|k| k.open_time < start);
+        let to = self.klines.partition_point(|k| k.open_time <= end);
+
+        &self.klines[from..to.max(from)]
+    }
+
+    /// Merges `klines` into the store, returning how many of them replaced an
+    /// already stored kline with the same open time.
+    pub fn insert(&mut self, klines: Vec<Kline>) -> usize {
+        self.klines.extend(klines);
+        normalize(&mut self.klines)
+    }
+
+    /// Returns the `(start, end)` open time ranges missing between stored
+    /// klines.
+    pub fn gaps(&self) -> Vec<(i64, i64)> {
+        let interval = self.interval;
+
+        self.klines
+            .windows(2)
+            .map(|w| (interval.next_open_time(w[0].open_time), w[1].open_time))
+            .filter(|&(next, open_time)| open_time > next)
+            .map(|(next, open_time)| (next, open_time - 1))
+            .collect()
+    }
+
+    /// Returns the open time ranges in `[start, end]` that are not covered by
+    /// the store and have to be downloaded.
+    pub fn missing_ranges(&self, start: i64, end: i64) -> Vec<(i64, i64)> {
+        let interval = self.interval;
+        let klines = self.range(start, end);
+
+        let (first, last) = match (klines.first(), klines.last()) {
+            (Some(first), Some(last)) => (first.open_time, last.open_time),
+            _ => return vec![(start, end)],
+        };
+
+        let mut ranges = Vec::new();
+
+        if first > start {
+            ranges.push((start, first - 1));
+        }
+
+        ranges.extend(
+            klines
+                .windows(2)
+                .map(|w| (interval.next_open_time(w[0].open_time), w[1].open_time))
+                .filter(|&(next, open_time)| open_time > next)
+                .map(|(next, open_time)| (next, open_time - 1)),
+        );
+
+        let next = interval.next_open_time(last);
+        if next <= end {
+            ranges.push((next, end));
+        }
+
+        ranges
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let tmp = self.path.with_extension("bin.tmp");
+        fs::write(&tmp, encode(&self.klines))?;
+        fs::rename(&tmp, &self.path)?;
+
+        Ok(())
+    }
+
+    /// Downloads only the klines missing in `[start, end]`, saving the store
+    /// after every page. Returns the number of downloaded klines.
+    pub fn sync(&mut self, binance: &Account, start: i64, end: i64) -> Result<usize> {
+        self.sync_with(
+            start,
+            end,
+            |store, from, to| store.fetch_page(binance, from, to),
+            |_, _| (),
+        )
+    }
+
+    /// Like [`sync`](Self::sync), requesting the pages from `[from, to]` with
+    /// `fetch` and calling `progress` after every saved page with the number
+    /// of klines downloaded so far and the open time of the next one.
+    pub fn sync_with<F, P>(
+        &mut self,
+        start: i64,
+        end: i64,
+        mut fetch: F,
+        mut progress: P,
+    ) -> Result<usize>
+    where
+        F: FnMut(&Self, i64, i64) -> Result<Vec<Kline>>,
+        P: FnMut(usize, i64),
+    {
+        let mut count = 0;
+
+        for (from, to) in self.missing_ranges(start, end) {
+            let mut from = from;
+
+            while from <= to {
+                let klines = fetch(self, from, to)?;
+                let len = klines.len();
+
+                if let Some(last) = klines.last() {
+                    from = self.interval.next_open_time(last.open_time);
+                } else {
+                    break;
+                }
+
+                count += len;
+                self.insert(klines);
+                self.save()?;
+                progress(count, from);
+
+                if len < PAGE_LIMIT as usize {
+                    break;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Requests a single page of closed klines starting at `start`.
+    pub fn fetch_page(&self, binance: &Account, start: i64, end: i64) -> Result<Vec<Kline>> {
+        let response = binance.get_kline_data(
+            &self.symbol,
+            self.interval,
+            Some(start),
+            Some(end),
+            Some(PAGE_LIMIT),
+        )?;
+
+        let now = Utc::now().timestamp_millis();
+        let interval = self.interval;
+
+        let mut klines = Kline::parse_2d_array(&response.bytes().unwrap(), PAGE_LIMIT as usize);
+        klines.retain(|k| interval.next_open_time(k.open_time) <= now);
+
+        Ok(klines)
+    }
+}
+
+/// Sorts `klines` by open time and removes duplicates, keeping the one
+/// inserted last. Returns the number of removed klines.
+fn normalize(klines: &mut Vec<Kline>) -> usize {
+    let len = klines.len();
+    klines.sort_by_key(|k| k.open_time);
+
+    let mut unique: Vec<Kline> = Vec::with_capacity(len);
+
+    for kline in klines.drain(..) {
+        match unique.last_mut() {
+            Some(last) if last.open_time == kline.open_time => *last = kline,
+            _ => unique.push(kline),
+        }
+    }
+
+    *klines = unique;
+    len - klines.len()
+}
+
+fn encode(klines: &[Kline]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + klines.len() * RECORD_SIZE);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+
+    for kline in klines {
+        bytes.extend_from_slice(&kline.open_time.to_le_bytes());
+        bytes.extend_from_slice(&kline.open.to_le_bytes());
+        bytes.extend_from_slice(&kline.high.to_le_bytes());
+        bytes.extend_from_slice(&kline.low.to_le_bytes());
+        bytes.extend_from_slice(&kline.close.to_le_bytes());
+        bytes.extend_from_slice(&kline.volume.to_le_bytes());
+    }
+
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Result<Vec<Kline>> {
+    if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+        return Err(Error::Corrupted(String::from("invalid header")));
+    }
+
+    if bytes[4] != VERSION {
+        return Err(Error::Corrupted(format!("unknown version {}", bytes[4])));
+    }
+
+    let body = &bytes[HEADER_SIZE..];
+
+    if !body.len().is_multiple_of(RECORD_SIZE) {
+        return Err(Error::Corrupted(format!(
+            "truncated record at byte {}",
+            HEADER_SIZE + body.len() / RECORD_SIZE * RECORD_SIZE
+        )));
+    }
+
+    let field = |record: &[u8], i: usize| {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&record[i * 8..(i + 1) * 8]);
+        buf
+    };
+
+    Ok(body
+        .chunks_exact(RECORD_SIZE)
+        .map(|record| Kline {
+            open_time: i64::from_le_bytes(field(record, 0)),
+            open: f64::from_le_bytes(field(record, 1)),
+            high: f64::from_le_bytes(field(record, 2)),
+            low: f64::from_le_bytes(field(record, 3)),
+            close: f64::from_le_bytes(field(record, 4)),
+            volume: f64::from_le_bytes(field(record, 5)),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    fn kline(open_time: i64, close: f64) -> Kline {
+        Kline {
+            open_time,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1f64,
+        }
+    }
+
+    fn store(name: &str) -> KlineStore {
+        let dir = std::env::temp_dir().join(format!("trading-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        KlineStore::open(&dir, "BNBUSDT", Interval::Hour(1)).unwrap()
+    }
+
+    #[test]
+    fn insert_sorts_and_replaces_duplicates() {
+        let mut store = store("insert");
+
+        assert_eq!(store.insert(vec![kline(2 * HOUR, 2f64), kline(0, 0f64)]), 0);
+        assert_eq!(
+            store.insert(vec![kline(HOUR, 1f64), kline(2 * HOUR, 3f64)]),
+            1
+        );
+
+        let times: Vec<i64> = store.klines().iter().map(|k| k.open_time).collect();
+        assert_eq!(times, vec![0, HOUR, 2 * HOUR]);
+        assert_eq!(store.klines()[2].close, 3f64);
+    }
+
+    #[test]
+    fn gaps_and_missing_ranges() {
+        let mut store = store("gaps");
+        store.insert(vec![
+            kline(2 * HOUR, 0f64),
+            kline(3 * HOUR, 0f64),
+            kline(6 * HOUR, 0f64),
+        ]);
+
+        assert_eq!(store.gaps(), vec![(4 * HOUR, 6 * HOUR - 1)]);
+        assert_eq!(
+            store.missing_ranges(0, 8 * HOUR),
+            vec![
+                (0, 2 * HOUR - 1),
+                (4 * HOUR, 6 * HOUR - 1),
+                (7 * HOUR, 8 * HOUR)
+            ]
+        );
+        assert_eq!(store.missing_ranges(2 * HOUR, 3 * HOUR), vec![]);
+        assert_eq!(
+            store.missing_ranges(10 * HOUR, 12 * HOUR),
+            vec![(10 * HOUR, 12 * HOUR)]
+        );
+        assert_eq!(store.range(HOUR, 3 * HOUR).len(), 2);
+    }
+
+    #[test]
+    fn save_and_reopen() {
+        let mut store = store("reopen");
+        store.insert(vec![kline(0, 1.5f64), kline(HOUR, 2.5f64)]);
+        store.save().unwrap();
+
+        let dir = store.path.parent().unwrap().to_path_buf();
+        let reopened = KlineStore::open(&dir, "BNBUSDT", Interval::Hour(1)).unwrap();
+        assert_eq!(reopened.klines(), store.klines());
+        assert_eq!(reopened.duplicates(), 0);
+
+        fs::write(&store.path, encode(&[kline(0, 1f64), kline(0, 2f64)])).unwrap();
+        let reopened = KlineStore::open(&dir, "BNBUSDT", Interval::Hour(1)).unwrap();
+        assert_eq!(reopened.duplicates(), 1);
+        assert_eq!(reopened.klines(), &[kline(0, 2f64)]);
+
+        fs::write(&store.path, &encode(store.klines())[..HEADER_SIZE + 10]).unwrap();
+        assert!(KlineStore::open(&dir, "BNBUSDT", Interval::Hour(1)).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Higher timeframes of a symbol, built from the klines of the interval it
+//! trades on.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::exchange::{Interval, Kline};
+use crate::indicators::ma::{MaType, MovingAverage};
+use crate::resample::Resampler;
+
+/// Only lets longs open while the close of the last complete `interval`
+/// kline is above its moving average, `4h:ema:50` on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrendFilter {
+    pub interval: Interval,
+    pub ma: MaType,
+    pub period: usize,
+}
+
+impl FromStr for TrendFilter {
+    type Err = String;
+
+    /// `<INTERVAL>[:<MA>[:<PERIOD>]]`, an EMA of 50 by default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let interval = parts.next().unwrap_or_default().parse()?;
+        let ma = parts.next().map_or(Ok(MaType::Ema), str::parse)?;
+        let period = match parts.next().map(str::parse) {
+            None => 50,
+            Some(Ok(period)) if period > 0 => period,
+            _ => return Err(format!("Invalid trend filter period in `{}`", s)),
+        };
+
+        if parts.next().is_some() {
+            return Err(format!("Invalid trend filter `{}`", s));
+        }
+
+        Ok(Self {
+            interval,
+            ma,
+            period,
+        })
+    }
+}
+
+impl fmt::Display for TrendFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}({})", self.interval, self.ma, self.period)
+    }
+}
+
+/// Indicators of a higher timeframe, moved only by its closed klines.
+pub trait TimeframeIndicators {
+    /// Adds a closed kline of the timeframe, after `prev` if there was one.
+    fn next(&mut self, kline: &Kline, prev: Option<&Kline>);
+}
+
+/// Last close against its moving average, the state of a [`TrendFilter`].
+pub struct MaTrend {
+    ma: MovingAverage,
+    period: usize,
+    close: Option<f64>,
+}
+
+impl MaTrend {
+    pub fn new(ma: MaType, period: usize) -> Self {
+        Self {
+            ma: ma.build(period),
+            period,
+            close: None,
+        }
+    }
+
+    /// Whether the last close is above the moving average, `false` until
+    /// both are known.
+    pub fn is_bullish(&self) -> bool {
+        matches!((self.close, self.ma.get()), (Some(close), Some(ma)) if close > ma)
+    }
+}
+
+impl TimeframeIndicators for MaTrend {
+    fn next(&mut self, kline: &Kline, _: Option<&Kline>) {
+        self.ma.next(kline.close, kline.volume);
+        self.close = Some(kline.close);
+    }
+}
+
+/// One higher timeframe. Klines of the trading interval are merged into the
+/// `interval` kline they belong to, and its indicators only move once that
+/// kline closes.
+pub struct Timeframe<I = MaTrend> {
+    interval: Interval,
+    indicators: I,
+    /// Built with the first kline of the trading interval.
+    resampler: Option<Resampler>,
+    /// Klines opening before this time were already seeded.
+    after: i64,
+    prev: Option<Kline>,
+}
+
+impl Timeframe {
+    pub fn new(filter: TrendFilter) -> Self {
+        Self::with_indicators(filter.interval, MaTrend::new(filter.ma, filter.period))
+    }
+
+    /// Milliseconds of history it takes to warm up the moving average, the
+    /// triple EMA needing the most.
+    pub fn warm_up(&self) -> i64 {
+        (3 * self.indicators.period as i64 + 1) * self.interval.to_millis()
+    }
+
+    pub fn is_bullish(&self) -> bool {
+        self.indicators.is_bullish()
+    }
+}
+
+impl<I: TimeframeIndicators> Timeframe<I> {
+    /// Runs `indicators` on the klines of `interval`.
+    pub fn with_indicators(interval: Interval, indicators: I) -> Self {
+        Self {
+            interval,
+            indicators,
+            resampler: None,
+            after: i64::MIN,
+            prev: None,
+        }
+    }
+
+    pub fn interval(&self) -> Interval {
+        self.interval
+    }
+
+    pub fn indicators(&self) -> &I {
+        &self.indicators
+    }
+
+    /// End of the complete `interval` klines at `now`, leaving out the one
+    /// still open.
+    pub fn seed_end(&self, now: i64) -> i64 {
+        self.interval.open_time(now) - 1
+    }
+
+    /// Feeds complete `interval` klines, e.g. fetched from the exchange to
+    /// warm up the indicators before trading starts.
+    pub fn seed(&mut self, klines: &[Kline]) {
+        for kline in klines {
+            self.close(kline);
+            self.after = self.interval.next_open_time(kline.open_time);
+        }
+    }
+
+    /// Adds a closed kline of the trading `interval`.
+    pub fn update(&mut self, kline: &Kline, interval: Interval) {
+        if kline.open_time < self.after {
+            return;
+        }
+
+        let to = self.interval;
+        let resampler = self.resampler.get_or_insert_with(|| {
+            Resampler::new(interval, to).expect("Invalid trend filter interval")
+        });
+
+        if let Some(kline) = resampler.next(kline) {
+            self.close(&kline);
+        }
+    }
+
+    fn close(&mut self, kline: &Kline) {
+        self.indicators.next(kline, self.prev.as_ref());
+        self.prev = Some(kline.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn kline(hour: i64, close: f64) -> Kline {
+        Kline {
+            open_time: hour * 3_600_000,
+            open: close,
+            high: close + 1f64,
+            low: close - 1f64,
+            close,
+            volume: 1f64,
+        }
+    }
+
+    #[test]
+    fn parse_filter() {
+        let filter: TrendFilter = "4h:sma:20".parse().unwrap();
+        assert_eq!(filter.interval, Interval::Hour(4));
+        assert_eq!((filter.ma, filter.period), (MaType::Sma, 20));
+
+        let filter: TrendFilter = "1d".parse().unwrap();
+        assert_eq!((filter.ma, filter.period), (MaType::Ema, 50));
+        assert_eq!(filter.to_string(), "1d EMA(50)");
+
+        assert!("4h:ema:0".parse::<TrendFilter>().is_err());
+        assert!("4h:ema:20:1".parse::<TrendFilter>().is_err());
+        assert!("4x".parse::<TrendFilter>().is_err());
+    }
+
+    #[test]
+    fn updates_on_higher_close_only() {
+        let hour = Interval::Hour(1);
+        let mut timeframe = Timeframe::new("4h:sma:2".parse().unwrap());
+
+        // Starts mid kline, the first 4h kline is skipped.
+        for h in 2..8 {
+            timeframe.update(&kline(h, 100f64 + h as f64), hour);
+        }
+        assert_eq!(timeframe.indicators.close, Some(107f64));
+        assert!(!timeframe.is_bullish());
+
+        // Closes at 107 and 111 against an SMA of 109.
+        for h in 8..12 {
+            timeframe.update(&kline(h, 100f64 + h as f64), hour);
+        }
+        assert!(timeframe.is_bullish());
+
+        // Nothing changes before the next 4h close.
+        timeframe.update(&kline(12, 50f64), hour);
+        assert!(timeframe.is_bullish());
+        for h in 13..16 {
+            timeframe.update(&kline(h, 50f64), hour);
+        }
+        assert!(!timeframe.is_bullish());
+
+        let mut seeded = Timeframe::new("4h:sma:2".parse().unwrap());
+        seeded.seed(&[kline(0, 100f64), kline(4, 90f64)]);
+        // Already part of the seeded klines.
+        seeded.update(&kline(7, 500f64), hour);
+        assert_eq!(seeded.indicators.close, Some(90f64));
+        assert!(!seeded.is_bullish());
+    }
+
+    #[test]
+    fn seeds_complete_klines_only() {
+        // On the 31st, 30 days back is still in the open month.
+        let now = Utc.with_ymd_and_hms(2021, 1, 31, 12, 0, 0).unwrap();
+        let month = Timeframe::new("1M".parse().unwrap());
+        let end = month.seed_end(now.timestamp_millis());
+        assert_eq!(
+            end,
+            Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0)
+                .unwrap()
+                .timestamp_millis()
+                - 1
+        );
+
+        let four_hours = Timeframe::new("4h".parse().unwrap());
+        assert_eq!(four_hours.seed_end(6 * 3_600_000), 4 * 3_600_000 - 1);
+    }
+
+    /// Ranges of the 4h klines and the close before each.
+    #[derive(Default)]
+    struct Ranges(Vec<(f64, Option<f64>)>);
+
+    impl TimeframeIndicators for Ranges {
+        fn next(&mut self, kline: &Kline, prev: Option<&Kline>) {
+            self.0.push((kline.high - kline.low, prev.map(|k| k.close)));
+        }
+    }
+
+    #[test]
+    fn runs_any_indicators() {
+        let mut timeframe = Timeframe::with_indicators(Interval::Hour(4), Ranges::default());
+        for h in 0..9 {
+            timeframe.update(&kline(h, 100f64 + h as f64), Interval::Hour(1));
+        }
+
+        // Two 4h klines spanning 99..104 and 103..108.
+        assert_eq!(
+            timeframe.indicators().0,
+            vec![(5f64, None), (5f64, Some(103f64))]
+        );
+    }
+}
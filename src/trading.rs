@@ -1,5 +1,7 @@
+use std::fmt;
 use std::fs::File;
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, RecvTimeoutError};
 use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -8,25 +10,35 @@ use chrono::{DateTime, FixedOffset, TimeZone, Utc};
 
 use crate::exchange::binance::{Account, Asset, SymbolString};
 use crate::exchange::{Interval, Kline};
-use crate::indicators::{BollingerBand, Dema, Dmi, TdSeq};
+use crate::indicators::ma::{MaType, MovingAverage};
+use crate::indicators::{BollingerBand, Dmi, TdSeq};
+use crate::limits::{Breach, Guard, Limits};
 use crate::parser::TomlParser;
+use crate::portfolio::Portfolio;
+use crate::report::{Report, Trade};
+use crate::resample::resample;
+use crate::risk::{Exit, Risk, RiskRules};
+use crate::sizing::{Budget, PositionSizing, Sizer};
+use crate::store::KlineStore;
 use crate::telegram;
+use crate::timeframe::{Timeframe, TrendFilter};
 
 pub struct Trader {
     binance: Account,
     telegram: telegram::Bot,
     start_time: DateTime<Utc>,
     symbols: Vec<Symbol>,
-    assets: Vec<Asset>,
+    portfolio: Portfolio,
     interval: Interval,
+    paper: bool,
+    guard: Guard,
 }
 
 impl Trader {
     pub fn new(binance: Account, interval: Interval) -> Self {
         let start_time = {
             let now = Utc::now().timestamp_millis();
-            let interval = interval.to_millis();
-            Utc.timestamp_millis(now + interval - now % interval)
+            Utc.timestamp_millis(interval.next_open_time(now))
         };
 
         let mut f = File::open("cache.toml").unwrap();
@@ -60,28 +72,132 @@ impl Trader {
             telegram,
             start_time,
             symbols,
-            assets,
+            portfolio: Portfolio::new("USDT").with_assets(assets),
             interval,
+            paper: false,
+            guard: Guard::new(Limits::default()),
         }
     }
 
+    /// Trades only `symbols` out of the ones in cache.toml.
+    pub fn with_symbols(mut self, symbols: &[String]) -> Result<Self, String> {
+        let cached = |s: &str| self.symbols.iter().any(|symbol| symbol.as_str() == s);
+        if let Some(missing) = symbols.iter().find(|s| !cached(s)) {
+            return Err(format!("{} is not in cache.toml", missing));
+        }
+
+        println!("[INFO] Trading: {}\n", symbols.join(", "));
+        self.symbols
+            .retain(|symbol| symbols.iter().any(|s| s == symbol.as_str()));
+        Ok(self)
+    }
+
+    pub fn with_parameters(mut self, parameters: Parameters) -> Self {
+        self.symbols = self
+            .symbols
+            .into_iter()
+            .map(|symbol| symbol.with_parameters(parameters))
+            .collect();
+        self
+    }
+
+    pub fn with_trend_filters(mut self, filters: &[TrendFilter]) -> Self {
+        if !filters.is_empty() {
+            print!("[INFO] Trend Filters: ");
+            let filters: Vec<_> = filters.iter().map(TrendFilter::to_string).collect();
+            println!("{}\n", filters.join(", "));
+        }
+
+        self.symbols = self
+            .symbols
+            .into_iter()
+            .map(|symbol| symbol.with_trend_filters(filters))
+            .collect();
+        self
+    }
+
+    pub fn with_risk(mut self, rules: RiskRules) -> Self {
+        if !rules.is_empty() {
+            println!("[INFO] Risk: {}\n", rules);
+        }
+
+        self.symbols = self
+            .symbols
+            .into_iter()
+            .map(|symbol| symbol.with_risk(rules))
+            .collect();
+        self
+    }
+
+    pub fn with_sizing(mut self, sizing: PositionSizing) -> Self {
+        if sizing != PositionSizing::default() {
+            println!("[INFO] Position Sizing: {}\n", sizing);
+        }
+
+        self.symbols = self
+            .symbols
+            .into_iter()
+            .map(|symbol| symbol.with_sizing(sizing))
+            .collect();
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        if !limits.is_empty() {
+            println!("[INFO] Limits: {}\n", limits);
+        }
+
+        self.guard = Guard::new(limits);
+        self
+    }
+
+    /// Asset the portfolio and its limits are valued in, USDT by default.
+    pub fn with_reference(mut self, reference: &str) -> Self {
+        self.portfolio.set_reference(reference);
+        self
+    }
+
+    /// Simulates orders against the cached balances instead of sending them
+    /// to the exchange.
+    pub fn paper(mut self) -> Self {
+        println!("[INFO] Paper trading, no orders will be sent\n");
+        self.paper = true;
+        self
+    }
+
     pub fn run(&mut self) {
+        self.seed_timeframes();
         let data = self.get_required_data();
+        let interval = self.interval;
 
         for (i, klines) in data.into_iter().enumerate() {
             let mut prev_kline = klines.first().unwrap();
 
             for kline in klines.iter().skip(1) {
                 self.symbols[i].indicators.update(kline, prev_kline);
+                self.symbols[i].risk.update(kline, prev_kline);
                 self.symbols[i].kline.update(kline);
+                self.symbols[i].update_timeframes(kline, interval);
 
                 prev_kline = kline;
             }
+
+            let symbol = &self.symbols[i];
+            self.portfolio
+                .set_price(symbol.base(), symbol.quote(), symbol.kline.close);
+        }
+
+        let unpriced = self.portfolio.unpriced();
+        if !unpriced.is_empty() {
+            println!(
+                "[WARN] No {} price for {}, left out of the equity\n",
+                self.portfolio.reference(),
+                unpriced.join(", ")
+            );
         }
 
         let (tx, rx) = mpsc::channel::<()>();
         let mut close_time = self.start_time.timestamp_millis() - 1;
-        let interval = self.interval.to_millis();
 
         let stdin = io::stdin();
         let handle = thread::spawn(move || {
@@ -102,12 +218,7 @@ impl Trader {
                         .as_millis() as i64) as u64,
             );
             match rx.recv_timeout(timeout) {
-                Ok(_) => {
-                    self.telegram.send_message("[INFO] Bot is offline!");
-                    println!("Exiting");
-                    handle.join().unwrap();
-                    break;
-                }
+                Ok(_) => break,
                 Err(RecvTimeoutError::Timeout) => {
                     for symbol in self.symbols.iter_mut() {
                         let response = self
@@ -124,7 +235,20 @@ impl Trader {
                         let kline = Kline::parse_array(&response.bytes().unwrap());
 
                         symbol.indicators.update(&kline, &symbol.kline);
+                        symbol.risk.update(&kline, &symbol.kline);
                         symbol.kline.update(&kline);
+                        symbol.update_timeframes(&kline, self.interval);
+                        self.portfolio
+                            .set_price(symbol.base(), symbol.quote(), kline.close);
+                    }
+
+                    let now = Utc::now().timestamp_millis();
+                    if let Some(breach) = self.guard.update(now, self.portfolio.equity()) {
+                        self.kill(breach);
+                        println!("[INFO] Press q to exit");
+                        // Returns right away if stdin was closed and the thread is gone.
+                        let _ = rx.recv();
+                        break;
                     }
 
                     let mut signals: Vec<Option<Signal>> = Vec::with_capacity(self.symbols.len());
@@ -135,8 +259,10 @@ impl Trader {
                                 Signal::Buy(_) => {
                                     let msg = format!(
                                         "[{}] {} Buy Signal",
-                                        FixedOffset::east(3 * 3600)
-                                            .timestamp_millis(symbol.kline.open_time),
+                                        FixedOffset::east_opt(3 * 3600)
+                                            .unwrap()
+                                            .timestamp_millis_opt(symbol.kline.open_time)
+                                            .unwrap(),
                                         symbol.as_str()
                                     );
                                     self.telegram.send_message(&msg);
@@ -144,12 +270,26 @@ impl Trader {
                                 Signal::Sell => {
                                     let msg = format!(
                                         "[{}] {} Sell Signal",
-                                        FixedOffset::east(3 * 3600)
-                                            .timestamp_millis(symbol.kline.open_time),
+                                        FixedOffset::east_opt(3 * 3600)
+                                            .unwrap()
+                                            .timestamp_millis_opt(symbol.kline.open_time)
+                                            .unwrap(),
                                         symbol.as_str()
                                     );
                                     self.telegram.send_message(&msg);
                                 }
+                                Signal::Exit(exit) => {
+                                    let msg = format!(
+                                        "[{}] {} {} Exit",
+                                        FixedOffset::east_opt(3 * 3600)
+                                            .unwrap()
+                                            .timestamp_millis_opt(symbol.kline.open_time)
+                                            .unwrap(),
+                                        symbol.as_str(),
+                                        exit
+                                    );
+                                    self.telegram.send_message(&msg);
+                                }
                             }
                         }
                         signals.push(signal);
@@ -158,16 +298,61 @@ impl Trader {
                     for (i, signal) in signals.into_iter().enumerate() {
                         if let Some(Signal::Buy(pos)) = signal {
                             self.buy(i, pos);
-                        } else if let Some(Signal::Sell) = signal {
+                        } else if let Some(Signal::Sell | Signal::Exit(_)) = signal {
                             self.sell(i);
                         }
                     }
 
-                    close_time += interval;
+                    close_time = self.interval.close_time(close_time + 1);
                 }
                 Err(e) => panic!("{}", e),
             }
         }
+
+        println!("{}\n", self.portfolio);
+        self.telegram.send_message("[INFO] Bot is offline!");
+        println!("Exiting");
+        handle.join().unwrap();
+    }
+
+    /// Stops trading after a loss limit is hit, selling every position if
+    /// the limits say so.
+    fn kill(&mut self, breach: Breach) {
+        let msg = format!("[KILL] {}, trading stopped", breach);
+        println!("{}", msg);
+        self.telegram.send_message(&msg);
+
+        if self.guard.limits().liquidate {
+            for i in 0..self.symbols.len() {
+                if self.symbols[i].position.is_some() {
+                    self.sell(i);
+                }
+            }
+            self.telegram.send_message("[KILL] Positions liquidated");
+        }
+    }
+
+    /// Warms up the higher timeframes with their last complete klines.
+    fn seed_timeframes(&mut self) {
+        let now = Utc::now().timestamp_millis();
+
+        for symbol in self.symbols.iter_mut() {
+            for timeframe in symbol.timeframes.iter_mut() {
+                let interval = timeframe.interval();
+                let response = self
+                    .binance
+                    .get_kline_data(
+                        symbol.name.as_str(),
+                        interval,
+                        None,
+                        Some(timeframe.seed_end(now)),
+                        Some(1000),
+                    )
+                    .expect("Could not get kline data");
+
+                timeframe.seed(&Kline::parse_2d_array(&response.bytes().unwrap(), 1000));
+            }
+        }
     }
 
     fn get_required_data(&self) -> Vec<Vec<Kline>> {
@@ -199,34 +384,54 @@ impl Trader {
             .iter()
             .filter(|symbol| symbol.position.is_none())
             .count();
+        let open_position_count = self.symbols.len() - close_position_count;
         let symbol = self.symbols.get_mut(symbol_index).unwrap();
-        let mut quote = None;
-        let mut base = None;
-
-        for asset in self.assets.iter_mut() {
-            if symbol.quote() == &asset.name {
-                quote = Some(asset);
-            } else if symbol.base() == &asset.name {
-                base = Some(asset);
-            }
+        let portfolio = &mut self.portfolio;
+
+        if !symbol.risk.is_ready() {
+            println!(
+                "[{}] {} Entry refused: the ATR of the risk rules is warming up",
+                Utc.timestamp_millis_opt(symbol.kline.open_time).unwrap(),
+                symbol.as_str(),
+            );
+            return;
         }
 
-        let quote = quote.unwrap();
-        let base = base.unwrap();
-        let mut quote_order_quantity = 0f64;
+        let quote_balance = portfolio.balance(symbol.quote());
+        let base_balance = portfolio.balance(symbol.base());
+        let budget = Budget {
+            free: quote_balance,
+            equity: portfolio.equity_in(symbol.quote()),
+            slots: close_position_count,
+            min_order: 10f64,
+        };
+        let quote_order_quantity =
+            symbol
+                .sizer
+                .size(&budget, symbol.kline.close, symbol.risk.atr());
+        let quote_order_quantity = (quote_order_quantity * 1e8f64).trunc() / 1e8f64;
 
-        for i in (1..=close_position_count).rev() {
-            quote_order_quantity = quote.balance / (i as f64);
-            quote_order_quantity = (quote_order_quantity * 1e8f64).trunc() / 1e8f64;
-            if quote_order_quantity > 10f64 {
-                break;
+        if quote_order_quantity > 10f64 {
+            let now = Utc::now().timestamp_millis();
+            let exposure =
+                (base_balance * symbol.kline.close + quote_order_quantity) / budget.equity;
+
+            if let Err(rejection) = self.guard.check_entry(now, open_position_count, exposure) {
+                println!(
+                    "[{}] {} Entry refused: {}",
+                    Utc.timestamp_millis_opt(symbol.kline.open_time).unwrap(),
+                    symbol.as_str(),
+                    rejection,
+                );
+                return;
             }
-        }
 
-        if quote_order_quantity > 10f64 {
-            self.binance
-                .market_buy(symbol.as_str(), quote_order_quantity)
-                .expect("Could not buy the coin");
+            if !self.paper {
+                self.binance
+                    .market_buy(symbol.as_str(), quote_order_quantity)
+                    .expect("Could not buy the coin");
+            }
+            self.guard.record_order(now);
 
             println!(
                 "[{}] Bought {} with {} {}",
@@ -238,15 +443,43 @@ impl Trader {
 
             symbol.net = symbol.kline.close;
             symbol.position = Some(pos);
+            symbol.risk.enter(symbol.kline.close);
 
-            base.balance = self
-                .binance
-                .get_balance(symbol.base())
-                .expect("Could not get balance");
-            quote.balance = self
-                .binance
-                .get_balance(symbol.quote())
-                .expect("Could not get balance");
+            if self.paper {
+                portfolio.buy(
+                    symbol.as_str(),
+                    symbol.base(),
+                    symbol.quote(),
+                    quote_order_quantity / symbol.kline.close,
+                    symbol.kline.close,
+                );
+            } else {
+                let base = self
+                    .binance
+                    .get_balance(symbol.base())
+                    .expect("Could not get balance");
+                let quote = self
+                    .binance
+                    .get_balance(symbol.quote())
+                    .expect("Could not get balance");
+
+                // Average fill price from the balance changes.
+                let quantity = base - base_balance;
+                let price = if quantity > 0f64 {
+                    (quote_balance - quote) / quantity
+                } else {
+                    symbol.kline.close
+                };
+                portfolio.buy(
+                    symbol.as_str(),
+                    symbol.base(),
+                    symbol.quote(),
+                    quantity,
+                    price,
+                );
+                portfolio.set_balance(symbol.base(), base);
+                portfolio.set_balance(symbol.quote(), quote);
+            }
         } else {
             println!(
                 "[{}] {} MIN_NOTIONAL Filter: {} < 10",
@@ -259,42 +492,67 @@ impl Trader {
 
     fn sell(&mut self, symbol_index: usize) {
         let symbol = self.symbols.get_mut(symbol_index).unwrap();
-        let mut quote = None;
-        let mut base = None;
-
-        for asset in self.assets.iter_mut() {
-            if symbol.quote() == &asset.name {
-                quote = Some(asset);
-            } else if symbol.base() == &asset.name {
-                base = Some(asset);
-            }
-        }
-
-        let quote = quote.unwrap();
-        let base = base.unwrap();
+        let portfolio = &mut self.portfolio;
 
-        base.balance = (base.balance * 10f64.powi(symbol.step_size)).trunc() / 10f64.powi(symbol.step_size);
+        let step = 10f64.powi(symbol.step_size);
+        let quantity = (portfolio.balance(symbol.base()) * step).trunc() / step;
+        let quote_balance = portfolio.balance(symbol.quote());
 
-        self.binance
-            .market_sell(symbol.as_str(), base.balance)
-            .expect("Could not sell the coin");
+        if !self.paper {
+            self.binance
+                .market_sell(symbol.as_str(), quantity)
+                .expect("Could not sell the coin");
+        }
+        self.guard.record_order(Utc::now().timestamp_millis());
 
         println!(
             "[{}] Sold {} {} NET: {:.1}%",
             Utc.timestamp_millis(symbol.kline.open_time),
-            base.balance,
+            quantity,
             symbol.base(),
             (symbol.kline.close / symbol.net - 1f64) * 100f64,
         );
+        symbol.sizer.record(symbol.kline.close / symbol.net - 1f64);
+
+        if self.paper {
+            portfolio.sell(
+                symbol.as_str(),
+                symbol.base(),
+                symbol.quote(),
+                quantity,
+                symbol.kline.close,
+            );
+        } else {
+            let quote = self
+                .binance
+                .get_balance(symbol.quote())
+                .expect("Could not get balance");
+            let price = if quantity > 0f64 {
+                (quote - quote_balance) / quantity
+            } else {
+                symbol.kline.close
+            };
+            portfolio.sell(
+                symbol.as_str(),
+                symbol.base(),
+                symbol.quote(),
+                quantity,
+                price,
+            );
+            // Dust below the step size stays in the base balance.
+            let base = self
+                .binance
+                .get_balance(symbol.base())
+                .expect("Could not get balance");
+            portfolio.set_balance(symbol.base(), base);
+            portfolio.set_balance(symbol.quote(), quote);
+        }
 
         symbol.net = 0f64;
         symbol.position = None;
+        symbol.risk.exit();
 
-        quote.balance = self
-            .binance
-            .get_balance(symbol.quote())
-            .expect("Could not get balance");
-        base.balance = 0f64;
+        println!("{}\n", portfolio);
     }
 }
 
@@ -302,10 +560,19 @@ pub struct Backtester {
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
     symbol: Symbol,
-    base: Asset,
-    quote: Asset,
+    /// Holds the base and quote assets, valued in the quote.
+    portfolio: Portfolio,
     interval: Interval,
+    /// Lower interval the klines are read at and resampled from.
+    source: Option<Interval>,
+    /// Kline store directory.
+    dir: PathBuf,
     net: f64,
+    entry_time: i64,
+    /// Fraction of the equity spent on the entry.
+    entry_fraction: f64,
+    report: Report,
+    verbose: bool,
 }
 
 impl Backtester {
@@ -315,165 +582,267 @@ impl Backtester {
         symbol: Symbol,
         interval: Interval,
     ) -> Self {
-        let base = Asset::new(symbol.base(), 0f64);
-        let quote = Asset::new(symbol.quote(), 100f64);
-
-        println!("[INFO] Symbol: {}\n", symbol.as_str());
-        println!("[INFO] Start Time: {}", start_time);
-        println!("[INFO] End Time: {}\n", end_time);
-        println!(
-            "[INFO] {} Balance: {}\n       {} Balance: {}",
-            &base.name, base.balance, &quote.name, quote.balance
-        );
-        println!("[INFO] Interval: {}\n", interval);
+        let portfolio =
+            Portfolio::new(symbol.quote()).with_assets(vec![Asset::new(symbol.quote(), 100f64)]);
+        let report = Report {
+            initial_equity: portfolio.equity(),
+            period: interval.to_millis(),
+            ..Report::default()
+        };
 
         Self {
             start_time,
             end_time,
             symbol,
-            base,
-            quote,
+            portfolio,
             interval,
+            source: None,
+            dir: PathBuf::from("./data"),
             net: 0f64,
+            entry_time: 0,
+            entry_fraction: 0f64,
+            report,
+            verbose: true,
         }
     }
 
-    pub fn run(&mut self, binance: Account) {
-        let klines = self.get_required_data(&binance);
-        let mut prev_kline = klines.first().unwrap();
+    /// Builds the tested klines out of the stored klines of a lower
+    /// `interval`, so that one minute store serves every interval.
+    pub fn source(mut self, interval: Interval) -> Self {
+        self.source = Some(interval);
+        self
+    }
+
+    /// Reads and downloads the klines in the store at `dir` instead of
+    /// `./data`.
+    pub fn dir(mut self, dir: &Path) -> Self {
+        self.dir = dir.to_path_buf();
+        self
+    }
+
+    /// Disables printing of trades, for running many backtests at once.
+    pub fn quiet(mut self) -> Self {
+        self.verbose = false;
+        self
+    }
+
+    pub fn run(&mut self, binance: &Account) -> Report {
+        println!("[INFO] Symbol: {}\n", self.symbol.as_str());
+        println!("[INFO] Start Time: {}", self.start_time);
+        println!("[INFO] End Time: {}\n", self.end_time);
+        println!(
+            "[INFO] {} Balance: {}\n       {} Balance: {}",
+            self.symbol.base(),
+            self.portfolio.balance(self.symbol.base()),
+            self.symbol.quote(),
+            self.portfolio.balance(self.symbol.quote())
+        );
+        println!("[INFO] Interval: {}\n", self.interval);
+
+        let klines = self.load_klines(binance);
+        let report = self.run_klines(&klines);
+
+        println!("{}", report);
+        println!("{}\n", self.portfolio);
+        report
+    }
+
+    /// Runs the strategy over `klines`, which should start early enough before
+    /// the start time to warm up the indicators.
+    pub fn run_klines(&mut self, klines: &[Kline]) -> Report {
+        let start_time = self.start_time.timestamp_millis();
+        let end_time = self.end_time.timestamp_millis();
+        let mut prev_kline = match klines.first() {
+            Some(kline) => kline,
+            None => return self.report.clone(),
+        };
 
         for kline in klines.iter().skip(1) {
+            if kline.open_time > end_time {
+                break;
+            }
+
             self.symbol.kline.update(kline);
             self.symbol.indicators.update(kline, prev_kline);
+            self.symbol.risk.update(kline, prev_kline);
+            self.symbol.update_timeframes(kline, self.interval);
+            self.portfolio
+                .set_price(self.symbol.base(), self.symbol.quote(), kline.close);
 
-            if self.start_time.timestamp_millis() <= kline.open_time {
+            if start_time <= kline.open_time {
                 match self.symbol.check_conditions() {
-                    Some(Signal::Buy(pos)) => {
-                        self.buy();
-                        self.symbol.net = self.symbol.kline.close;
-                        self.symbol.position = Some(pos);
-                    }
+                    Some(Signal::Buy(pos)) => self.buy(pos),
                     Some(Signal::Sell) => {
                         self.sell();
                         self.symbol.position = None;
+                        self.symbol.risk.exit();
+                    }
+                    Some(Signal::Exit(exit)) => {
+                        if self.verbose {
+                            println!("[INFO] {}", exit);
+                        }
+                        self.sell();
+                        self.symbol.position = None;
+                        self.symbol.risk.exit();
                     }
                     None => (),
                 }
+
+                self.report.equity.push(self.portfolio.equity());
             }
 
             prev_kline = kline;
         }
 
-        if self.symbol.position.is_none() {
-            println!("ROI: {:.1}%", (self.quote.balance / 100f64 - 1f64) * 100f64);
-        } else {
-            println!(
-                "ROI: {:.1}%",
-                (self.base.balance * klines.last().unwrap().close / 100f64 - 1f64) * 100f64
-            );
-        }
+        self.report.clone()
     }
 
-    fn get_required_data(&self, binance: &Account) -> Vec<Kline> {
+    /// Loads the klines of the tested range from the local store, downloading
+    /// the missing ones, with 1000 extra klines to warm up the indicators, or
+    /// more if the trend filters need them.
+    pub fn load_klines(&self, binance: &Account) -> Vec<Kline> {
         let interval: i64 = self.interval.to_millis();
-        let prev_time = self.start_time.timestamp_millis() - interval;
-
-        let data_count =
-            (self.end_time.timestamp_millis() - self.start_time.timestamp_millis()) / interval;
-
-        let iteration = if data_count % 1000 == 0 {
-            data_count / 1000
-        } else {
-            data_count / 1000 + 1
-        };
-
-        let path = format!(
-            "./data/{}_{}_{}.txt",
-            self.symbol.as_str(),
-            self.start_time.naive_utc().date(),
-            self.interval
-        );
+        let warm_up = self
+            .symbol
+            .timeframes
+            .iter()
+            .map(Timeframe::warm_up)
+            .fold(1000 * interval, i64::max);
+        let prev_time = self.start_time.timestamp_millis() - warm_up;
+        let end_time = self.end_time.timestamp_millis();
+
+        let source = self.source.unwrap_or(self.interval);
+        let mut store = KlineStore::open(&self.dir, self.symbol.as_str(), source)
+            .expect("Could not open kline store");
+        if store.duplicates() > 0 {
+            println!(
+                "[WARN] {} has {} duplicate klines, dropped",
+                self.symbol.as_str(),
+                store.duplicates()
+            );
+        }
+        store
+            .sync(binance, prev_time, end_time)
+            .expect("Could not get kline data");
 
-        match std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create_new(true)
-            .open(&path)
-        {
-            Ok(mut f) => {
-                let response = binance
-                    .get_kline_data(
-                        self.symbol.as_str(),
-                        self.interval,
-                        None,
-                        Some(prev_time),
-                        Some(1000),
-                    )
-                    .expect("Could not get kline data");
-                f.write_all(&response.bytes().unwrap()).unwrap();
-
-                for i in 0..iteration {
-                    let start_time = self.start_time.timestamp_millis() + (i * interval * 1000);
-                    let response = binance
-                        .get_kline_data(
-                            self.symbol.as_str(),
-                            self.interval,
-                            Some(start_time),
-                            Some(self.end_time.timestamp_millis()),
-                            Some(1000),
-                        )
-                        .expect("Could not get kline data");
-                    f.write_all(&response.bytes().unwrap()).unwrap();
-                }
+        for (start, end) in store.gaps() {
+            println!(
+                "[WARN] {} has no klines between {} and {}",
+                self.symbol.as_str(),
+                Utc.timestamp_millis_opt(start).unwrap(),
+                Utc.timestamp_millis_opt(end).unwrap(),
+            );
+        }
 
-                f.sync_all().unwrap();
-                f.seek(SeekFrom::Start(0)).unwrap();
-                let mut bytes = Vec::new();
-                f.read_to_end(&mut bytes).unwrap();
+        let klines = store.range(prev_time, end_time);
+        match self.source {
+            Some(source) => {
+                resample(klines, source, self.interval).expect("Could not resample klines")
+            }
+            None => klines.to_vec(),
+        }
+    }
 
-                Kline::parse_2d_array(&bytes, (data_count + 1000) as usize)
+    /// Enters at the close, unless the sizing spends nothing or the risk
+    /// rules wait for the ATR.
+    fn buy(&mut self, pos: Position) {
+        if !self.symbol.risk.is_ready() {
+            if self.verbose {
+                println!(
+                    "[INFO] SKIP {}: the ATR of the risk rules is warming up",
+                    Utc.timestamp_millis_opt(self.symbol.kline.open_time)
+                        .unwrap()
+                );
             }
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::AlreadyExists => {
-                    let mut f = std::fs::File::open(&path).unwrap();
-                    let mut bytes = Vec::new();
-                    f.read_to_end(&mut bytes).unwrap();
+            return;
+        }
 
-                    Kline::parse_2d_array(&bytes, (data_count + 1000) as usize)
-                }
-                _ => panic!("{:?}", e),
-            },
+        let budget = Budget {
+            free: self.portfolio.balance(self.symbol.quote()),
+            equity: self.portfolio.equity(),
+            slots: 1,
+            min_order: 0f64,
+        };
+        let amount =
+            self.symbol
+                .sizer
+                .size(&budget, self.symbol.kline.close, self.symbol.risk.atr());
+
+        if amount <= 0f64 {
+            if self.verbose {
+                println!(
+                    "[INFO] SKIP {}: nothing to spend",
+                    Utc.timestamp_millis_opt(self.symbol.kline.open_time).unwrap()
+                );
+            }
+            return;
         }
-    }
 
-    fn buy(&mut self) {
         self.net = self.symbol.kline.close;
-        self.base.balance = self.quote.balance / self.symbol.kline.close;
-        self.quote.balance = 0f64;
-        println!(
-            "[INFO] BUY  {}: PRICE: {:.4}",
-            Utc.timestamp_millis(self.symbol.kline.open_time),
-            self.symbol.kline.close
+        self.entry_time = self.symbol.kline.open_time;
+        self.entry_fraction = amount / budget.equity;
+        self.symbol.net = self.symbol.kline.close;
+        self.symbol.position = Some(pos);
+        self.symbol.risk.enter(self.symbol.kline.close);
+        self.portfolio.buy(
+            self.symbol.as_str(),
+            self.symbol.base(),
+            self.symbol.quote(),
+            amount / self.symbol.kline.close,
+            self.symbol.kline.close,
         );
+
+        if self.verbose {
+            println!(
+                "[INFO] BUY  {}: PRICE: {:.4}",
+                Utc.timestamp_millis_opt(self.symbol.kline.open_time).unwrap(),
+                self.symbol.kline.close
+            );
+        }
     }
 
     fn sell(&mut self) {
+        self.report.trades.push(Trade {
+            entry_time: self.entry_time,
+            exit_time: self.symbol.kline.open_time,
+            entry_price: self.net,
+            exit_price: self.symbol.kline.close,
+            fraction: self.entry_fraction,
+        });
+
+        self.symbol
+            .sizer
+            .record(self.symbol.kline.close / self.net - 1f64);
         self.net = (self.symbol.kline.close / self.net - 1f64) * 100f64;
-        self.quote.balance = self.base.balance * self.symbol.kline.close;
-        self.base.balance = 0f64;
-        println!(
-            "[INFO] SELL {}: PRICE: {:.4}    NET: {:.4}\n",
-            Utc.timestamp_millis(self.symbol.kline.open_time),
+        self.portfolio.sell(
+            self.symbol.as_str(),
+            self.symbol.base(),
+            self.symbol.quote(),
+            self.portfolio.balance(self.symbol.base()),
             self.symbol.kline.close,
-            self.net,
         );
+
+        if self.verbose {
+            println!(
+                "[INFO] SELL {}: PRICE: {:.4}    NET: {:.4}\n",
+                Utc.timestamp_millis_opt(self.symbol.kline.open_time).unwrap(),
+                self.symbol.kline.close,
+                self.net,
+            );
+        }
     }
 }
 
 pub struct Symbol {
     name: SymbolString,
+    parameters: Parameters,
     indicators: Indicators,
     kline: Kline,
+    /// Higher timeframes filtering the entries.
+    timeframes: Vec<Timeframe>,
+    /// Exits on top of the strategy.
+    risk: Risk,
+    sizer: Sizer,
     step_size: i32,
     position: Option<Position>,
     net: f64,
@@ -483,8 +852,12 @@ impl Symbol {
     pub fn new(base: &str, quote: &str) -> Self {
         Self {
             name: SymbolString::new(base, quote),
+            parameters: Parameters::default(),
             indicators: Indicators::default(),
             kline: Kline::default(),
+            timeframes: Vec::new(),
+            risk: Risk::default(),
+            sizer: Sizer::default(),
             step_size: 8,
             position: Option::default(),
             net: 0f64,
@@ -494,23 +867,63 @@ impl Symbol {
     pub fn from_string(inner: String, mid: usize, step_size: i32) -> Self {
         Self {
             name: SymbolString::from_raw_parts(inner, mid),
+            parameters: Parameters::default(),
             indicators: Indicators::default(),
             kline: Kline::default(),
+            timeframes: Vec::new(),
+            risk: Risk::default(),
+            sizer: Sizer::default(),
             step_size,
             position: Option::default(),
             net: 0f64,
         }
     }
 
+    pub fn with_parameters(mut self, parameters: Parameters) -> Self {
+        self.indicators = Indicators::new(&parameters);
+        self.parameters = parameters;
+        self
+    }
+
+    pub fn with_trend_filters(mut self, filters: &[TrendFilter]) -> Self {
+        self.timeframes = filters.iter().copied().map(Timeframe::new).collect();
+        self
+    }
+
+    pub fn with_risk(mut self, rules: RiskRules) -> Self {
+        self.risk = Risk::new(rules);
+        self
+    }
+
+    pub fn with_sizing(mut self, sizing: PositionSizing) -> Self {
+        self.sizer = Sizer::new(sizing);
+        self
+    }
+
+    /// Adds a closed kline of the trading `interval` to the higher
+    /// timeframes.
+    fn update_timeframes(&mut self, kline: &Kline, interval: Interval) {
+        for timeframe in self.timeframes.iter_mut() {
+            timeframe.update(kline, interval);
+        }
+    }
+
     fn check_conditions(&self) -> Option<Signal> {
+        let p = &self.parameters;
+
+        if let Some(exit) = self.risk.check(&self.kline) {
+            return Some(Signal::Exit(exit));
+        }
+
         if let (Some((basis, upper, lower)), (Some(adx), Some(pdi), Some(mdi)), Some(dema)) = (
             self.indicators.bb.get(),
             self.indicators.dmi.get(),
             self.indicators.dema.get(),
         ) {
             match self.position {
+                None if !self.timeframes.iter().all(Timeframe::is_bullish) => None,
                 None => {
-                    let bound = if adx > 15f64 {
+                    let bound = if adx > p.adx_trend {
                         lower - self.indicators.bb.dev().unwrap() / 2f64
                     } else {
                         lower
@@ -519,8 +932,8 @@ impl Symbol {
                     let buy_the_dip = self.kline.close < bound;
 
                     let to_the_moon = pdi > mdi
-                        && adx > 25f64
-                        && adx < 40f64
+                        && adx > p.adx_strong
+                        && adx < p.adx_extreme
                         && adx > dema
                         && self.kline.low > basis
                         && self.kline.low < basis + self.indicators.bb.dev().unwrap() / 2f64;
@@ -545,8 +958,10 @@ impl Symbol {
                     }
                 }
                 Some(Position::Mean) => {
-                    let at_the_moon =
-                        pdi > mdi && adx > 25f64 && dema > adx && self.indicators.was_perfect;
+                    let at_the_moon = pdi > mdi
+                        && adx > p.adx_strong
+                        && dema > adx
+                        && self.indicators.was_perfect;
 
                     if at_the_moon {
                         Some(Signal::Sell)
@@ -573,27 +988,83 @@ impl Symbol {
     }
 }
 
+/// Tunable periods and thresholds of the strategy in
+/// [`Symbol::check_conditions`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parameters {
+    pub dmi_period: usize,
+    /// Moving average of the ADX that a trend must stay above.
+    pub adx_ma: MaType,
+    pub dema_period: usize,
+    pub bb_period: usize,
+    pub bb_multiplier: f64,
+    /// ADX above which the dip entry waits for an extra half deviation.
+    pub adx_trend: f64,
+    /// ADX above which a trend is strong enough to ride.
+    pub adx_strong: f64,
+    /// ADX above which a trend is too late to enter.
+    pub adx_extreme: f64,
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Self {
+            dmi_period: 14,
+            adx_ma: MaType::Dema,
+            dema_period: 9,
+            bb_period: 20,
+            bb_multiplier: 2f64,
+            adx_trend: 15f64,
+            adx_strong: 25f64,
+            adx_extreme: 40f64,
+        }
+    }
+}
+
+impl fmt::Display for Parameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DMI: {:>2}  {}: {:>2}  BB: {:>2}/{:.2}  ADX: {}/{}/{}",
+            self.dmi_period,
+            self.adx_ma,
+            self.dema_period,
+            self.bb_period,
+            self.bb_multiplier,
+            self.adx_trend,
+            self.adx_strong,
+            self.adx_extreme,
+        )
+    }
+}
+
 struct Indicators {
     dmi: Dmi,
-    bb: BollingerBand<20>,
-    dema: Dema,
+    bb: BollingerBand,
+    dema: MovingAverage,
     td_seq: TdSeq,
+    adx_strong: f64,
     was_perfect: bool,
 }
 
 impl Default for Indicators {
     fn default() -> Self {
+        Self::new(&Parameters::default())
+    }
+}
+
+impl Indicators {
+    fn new(parameters: &Parameters) -> Self {
         Self {
-            dmi: Dmi::new(14),
-            bb: BollingerBand::new(2f64),
-            dema: Dema::new(9),
+            dmi: Dmi::new(parameters.dmi_period),
+            bb: BollingerBand::new(parameters.bb_period, parameters.bb_multiplier),
+            dema: parameters.adx_ma.build(parameters.dema_period),
             td_seq: TdSeq::new(),
+            adx_strong: parameters.adx_strong,
             was_perfect: false,
         }
     }
-}
 
-impl Indicators {
     pub fn update(&mut self, kline: &Kline, prev_kline: &Kline) {
         self.dmi.next(
             kline.high,
@@ -605,13 +1076,13 @@ impl Indicators {
         self.bb.next(kline.close);
 
         if let Some(adx) = self.dmi.get().0 {
-            self.dema.next(adx);
+            self.dema.next(adx, kline.volume);
         }
 
         self.td_seq.next(kline.high, kline.low, kline.close);
 
         if let ((Some(adx), Some(pdi), Some(mdi)), Some(dema)) = (self.dmi.get(), self.dema.get()) {
-            if self.td_seq.sell_perfect() && adx > dema && pdi > mdi && adx > 25f64 {
+            if self.td_seq.sell_perfect() && adx > dema && pdi > mdi && adx > self.adx_strong {
                 self.was_perfect = true;
             }
 
@@ -626,6 +1097,8 @@ impl Indicators {
 enum Signal {
     Buy(Position),
     Sell,
+    /// Sell forced by the risk rules.
+    Exit(Exit),
 }
 
 #[derive(Clone, Copy)]
@@ -0,0 +1,190 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::exchange::Kline;
+use crate::optimizer::Optimizer;
+use crate::report::Report;
+use crate::trading::Parameters;
+
+/// An in-sample range used for optimizing and the out-of-sample range right
+/// after it used for evaluating. Both ranges exclude their end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Window {
+    pub in_sample: (DateTime<Utc>, DateTime<Utc>),
+    pub out_of_sample: (DateTime<Utc>, DateTime<Utc>),
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowResult {
+    pub window: Window,
+    pub parameters: Parameters,
+    pub in_sample: Report,
+    pub out_of_sample: Report,
+}
+
+#[derive(Clone, Debug)]
+pub struct WalkForwardReport {
+    pub windows: Vec<WindowResult>,
+    /// Out-of-sample reports of all windows chained into one equity curve.
+    pub out_of_sample: Report,
+}
+
+/// Rolling walk-forward analysis: optimizes on every in-sample window and
+/// evaluates the winner on the out-of-sample window that follows it, then
+/// moves both forward by the out-of-sample length.
+pub struct WalkForward {
+    optimizer: Optimizer,
+    in_sample: Duration,
+    out_of_sample: Duration,
+}
+
+impl WalkForward {
+    pub fn new(optimizer: Optimizer, in_sample: Duration, out_of_sample: Duration) -> Self {
+        assert!(
+            in_sample > Duration::zero() && out_of_sample > Duration::zero(),
+            "walk-forward windows must not be empty"
+        );
+
+        Self {
+            optimizer,
+            in_sample,
+            out_of_sample,
+        }
+    }
+
+    /// Splits `[start_time, end_time)` into windows. The last out-of-sample
+    /// range is cut short at `end_time`.
+    pub fn windows(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Vec<Window> {
+        let mut windows = Vec::new();
+        let mut in_sample_start = start_time;
+
+        loop {
+            let in_sample_end = in_sample_start + self.in_sample;
+            if in_sample_end >= end_time {
+                break;
+            }
+
+            let out_of_sample_end = (in_sample_end + self.out_of_sample).min(end_time);
+            windows.push(Window {
+                in_sample: (in_sample_start, in_sample_end),
+                out_of_sample: (in_sample_end, out_of_sample_end),
+            });
+
+            in_sample_start += self.out_of_sample;
+        }
+
+        windows
+    }
+
+    /// Runs the analysis over `klines`, which should cover `start_time` to
+    /// `end_time` plus the indicator warm up before it.
+    pub fn run(
+        &self,
+        candidates: &[Parameters],
+        klines: &[Kline],
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> WalkForwardReport {
+        let last = Duration::milliseconds(1);
+        let mut windows = Vec::new();
+
+        for window in self.windows(start_time, end_time) {
+            let (start, end) = window.in_sample;
+            let (parameters, in_sample) = match self
+                .optimizer
+                .with_range(start, end - last)
+                .run(candidates, klines)
+                .into_iter()
+                .next()
+            {
+                Some(best) => best,
+                None => break,
+            };
+
+            let (start, end) = window.out_of_sample;
+            let out_of_sample = self
+                .optimizer
+                .with_range(start, end - last)
+                .backtest(parameters, klines);
+
+            windows.push(WindowResult {
+                window,
+                parameters,
+                in_sample,
+                out_of_sample,
+            });
+        }
+
+        let reports: Vec<&Report> = windows.iter().map(|w| &w.out_of_sample).collect();
+
+        WalkForwardReport {
+            out_of_sample: stitch(&reports),
+            windows,
+        }
+    }
+}
+
+/// Chains consecutive reports into one, compounding each report's returns
+/// onto the final equity of the previous one. A position still open at the
+/// end of a report is counted at its closing value.
+pub fn stitch(reports: &[&Report]) -> Report {
+    let mut stitched = match reports.first() {
+        Some(first) => Report {
+            initial_equity: first.initial_equity,
+            period: first.period,
+            ..Report::default()
+        },
+        None => return Report::default(),
+    };
+
+    for report in reports {
+        let scale = stitched.final_equity() / report.initial_equity;
+
+        stitched.trades.extend_from_slice(&report.trades);
+        stitched
+            .equity
+            .extend(report.equity.iter().map(|e| e * scale));
+    }
+
+    stitched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::Interval;
+    use crate::report::Metric;
+    use chrono::TimeZone;
+
+    #[test]
+    fn rolling_windows() {
+        let start = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2021, 1, 11, 0, 0, 0).unwrap();
+        let optimizer = Optimizer::new("BNB", "USDT", Interval::Hour(1), start, end, Metric::Roi);
+        let walk_forward = WalkForward::new(optimizer, Duration::days(4), Duration::days(4));
+
+        let windows = walk_forward.windows(start, end);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].in_sample, (start, start + Duration::days(4)));
+        assert_eq!(windows[1].in_sample.0, windows[0].out_of_sample.0);
+        assert_eq!(windows[1].out_of_sample.1, end);
+    }
+
+    #[test]
+    fn stitch_compounds_returns() {
+        let first = Report {
+            equity: vec![110f64, 120f64],
+            initial_equity: 100f64,
+            ..Report::default()
+        };
+        let second = Report {
+            equity: vec![50f64, 150f64],
+            initial_equity: 100f64,
+            ..Report::default()
+        };
+
+        let stitched = stitch(&[&first, &second]);
+        assert_eq!(stitched.equity, vec![110f64, 120f64, 60f64, 180f64]);
+        assert!((stitched.roi() - 0.8f64).abs() < 1e-12);
+        assert!((stitched.max_drawdown() - 0.5f64).abs() < 1e-12);
+    }
+}